@@ -1,4 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rkit::commands::ls::ListOptions;
+use rkit::workspace::Workspace;
 use std::env;
 use std::path::PathBuf;
 
@@ -30,8 +32,14 @@ fn bench_ls_command(c: &mut Criterion) {
         }
     };
 
+    let workspace = Workspace::single(&project_root, None);
     c.bench_function("ls_command", |b| {
-        b.iter(|| rkit::commands::ls::list_repos(black_box(&project_root), black_box(false), None))
+        b.iter(|| {
+            rkit::commands::ls::list_repos(
+                black_box(&workspace),
+                black_box(&ListOptions::default()),
+            )
+        })
     });
 }
 
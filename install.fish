@@ -12,14 +12,6 @@ if not command -v rkit > /dev/null
     exit 1
 end
 
-# Check if fzf is installed
-if not command -v fzf > /dev/null
-    echo "Error: fzf is not installed"
-    echo "Please install fzf first by following the instructions at:"
-    echo "https://junegunn.github.io/fzf/"
-    exit 1
-end
-
 # Create functions directory if it doesn't exist
 set functions_dir "$HOME/.config/fish/functions"
 if not test -d "$functions_dir"
@@ -57,22 +49,14 @@ end"
 
 # Create cdc function
 create_function_file "cdc" "function cdc
-    set -l query \$argv[1]
-    if test -n \"\$query\"
-        cd (rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")
-    else
-        cd (rkit ls -f | fzf --preview 'rkit view {}')
-    end
+    set -l dir (rkit pick \$argv[1])
+    and cd \"\$dir\"
 end"
 
 # Create edit function
 create_function_file "edit" "function edit
-    set -l query \$argv[1]
-    if test -n \"\$query\"
-        code (rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")
-    else
-        code (rkit ls -f | fzf --preview 'rkit view {}')
-    end
+    set -l dir (rkit pick \$argv[1])
+    and code \"\$dir\"
 end"
 
 # Create completions file
@@ -46,14 +46,6 @@ if ! command -v rkit &> /dev/null; then
     exit 1
 fi
 
-# Check if fzf is installed
-if ! command -v fzf &> /dev/null; then
-    echo "Error: fzf is not installed"
-    echo "Please install fzf first by following the instructions at:"
-    echo "https://junegunn.github.io/fzf/"
-    exit 1
-fi
-
 # Function to prompt user for input
 prompt_user() {
     local prompt="$1"
@@ -121,21 +113,13 @@ clone() {
         echo
         if [ "$SHELL_NAME" = "bash" ]; then
             echo "function cdc {"
-            echo "    local query=\"\$1\""
-            echo "    if [ -n \"\$query\" ]; then"
-            echo "        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\""
-            echo "    else"
-            echo "        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}')\""
-            echo "    fi"
+            echo "    local dir"
+            echo "    dir=\"\$(rkit pick \"\$1\")\" && cd \"\$dir\""
             echo "}"
         else
             echo "cdc() {"
-            echo "    local query=\"\$1\""
-            echo "    if [ -n \"\$query\" ]; then"
-            echo "        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\""
-            echo "    else"
-            echo "        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}')\""
-            echo "    fi"
+            echo "    local dir"
+            echo "    dir=\"\$(rkit pick \"\$1\")\" && cd \"\$dir\""
             echo "}"
         fi
         echo
@@ -144,22 +128,14 @@ clone() {
             if [ "$SHELL_NAME" = "bash" ]; then
                 functions_to_add+="
 function cdc {
-    local query=\"\$1\"
-    if [ -n \"\$query\" ]; then
-        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\"
-    else
-        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}')\"
-    fi
+    local dir
+    dir=\"\$(rkit pick \"\$1\")\" && cd \"\$dir\"
 }"
             else
                 functions_to_add+="
 cdc() {
-    local query=\"\$1\"
-    if [ -n \"\$query\" ]; then
-        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\"
-    else
-        cd \"\$(rkit ls -f | fzf --preview 'rkit view {}')\"
-    fi
+    local dir
+    dir=\"\$(rkit pick \"\$1\")\" && cd \"\$dir\"
 }"
             fi
         fi
@@ -173,21 +149,13 @@ cdc() {
         echo
         if [ "$SHELL_NAME" = "bash" ]; then
             echo "function edit {"
-            echo "    local query=\"\$1\""
-            echo "    if [ -n \"\$query\" ]; then"
-            echo "        code \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\""
-            echo "    else"
-            echo "        code \"\$(rkit ls -f | fzf --preview 'rkit view {}')\""
-            echo "    fi"
+            echo "    local dir"
+            echo "    dir=\"\$(rkit pick \"\$1\")\" && code \"\$dir\""
             echo "}"
         else
             echo "edit() {"
-            echo "    local query=\"\$1\""
-            echo "    if [ -n \"\$query\" ]; then"
-            echo "        code \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\""
-            echo "    else"
-            echo "        code \"\$(rkit ls -f | fzf --preview 'rkit view {}')\""
-            echo "    fi"
+            echo "    local dir"
+            echo "    dir=\"\$(rkit pick \"\$1\")\" && code \"\$dir\""
             echo "}"
         fi
         echo
@@ -196,22 +164,14 @@ cdc() {
             if [ "$SHELL_NAME" = "bash" ]; then
                 functions_to_add+="
 function edit {
-    local query=\"\$1\"
-    if [ -n \"\$query\" ]; then
-        code \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\"
-    else
-        code \"\$(rkit ls -f | fzf --preview 'rkit view {}')\"
-    fi
+    local dir
+    dir=\"\$(rkit pick \"\$1\")\" && code \"\$dir\"
 }"
             else
                 functions_to_add+="
 edit() {
-    local query=\"\$1\"
-    if [ -n \"\$query\" ]; then
-        code \"\$(rkit ls -f | fzf --preview 'rkit view {}' --query \"\$query\")\"
-    else
-        code \"\$(rkit ls -f | fzf --preview 'rkit view {}')\"
-    fi
+    local dir
+    dir=\"\$(rkit pick \"\$1\")\" && code \"\$dir\"
 }"
             fi
         fi
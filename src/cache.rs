@@ -1,13 +1,15 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
-use std::fs;
+use std::fs::{self, File};
 use std::path::{Component, Path, PathBuf};
+use std::process;
 use std::sync::RwLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 use crate::error::RkitError;
+use crate::scanner::RepoKind;
 
 /// Configuration options for the cache
 #[derive(Debug, Clone)]
@@ -75,14 +77,44 @@ pub struct CacheEntry {
     pub last_checked: u64,
 }
 
+/// Index of every repository discovered under a project root
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RootIndex {
+    /// Repositories found by the last full scan of the root
+    pub repos: Vec<PathBuf>,
+    /// Walker settings the scan was performed with
+    pub scan_key: String,
+    /// Last time the root was fully scanned
+    pub last_scanned: u64,
+    /// Modification time (nanoseconds since epoch, 0 if missing) of every
+    /// `.rkitignore` file the scan depended on
+    #[serde(default)]
+    pub ignore_files: HashMap<PathBuf, u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CacheData {
     entries: HashMap<PathBuf, CacheEntry>,
+    #[serde(default)]
+    roots: HashMap<PathBuf, RootIndex>,
     version: u32,
 }
 
+#[derive(Debug, Default)]
+struct LoadedCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    roots: HashMap<PathBuf, RootIndex>,
+}
+
+/// Current on-disk cache format version
+const CACHE_VERSION: u32 = 2;
+
+/// Extension of the file locked while a store is read, changed and written
+pub(crate) const LOCK_EXTENSION: &str = "lock";
+
 pub struct Cache {
     entries: RwLock<HashMap<PathBuf, CacheEntry>>,
+    roots: RwLock<HashMap<PathBuf, RootIndex>>,
     cache_path: PathBuf,
     pub config: CacheConfig,
 }
@@ -108,15 +140,16 @@ impl Cache {
                 }
             });
 
-        let entries = match load_cache(&cache_path) {
-            Ok(entries) => entries,
+        let loaded = match load_cache(&cache_path) {
+            Ok(loaded) => loaded,
             Err(e) => {
                 log::warn!("Failed to load cache: {}", e);
-                HashMap::new()
+                LoadedCache::default()
             }
         };
         Self {
-            entries: RwLock::new(entries),
+            entries: RwLock::new(loaded.entries),
+            roots: RwLock::new(loaded.roots),
             cache_path,
             config,
         }
@@ -135,10 +168,8 @@ impl Cache {
             }
         }
 
-        // Entry exists but is invalid — acquire write lock to evict
-        let mut entries = self.entries.write().ok()?;
-        entries.remove(path);
-        let _ = self.save_with_entries(&entries);
+        // Entry exists but is invalid — evict it
+        let _ = self.modify(|data| Ok(data.entries.remove(path).is_some()));
         None
     }
 
@@ -151,46 +182,34 @@ impl Cache {
             )));
         }
 
-        let mut entries = self
-            .entries
-            .write()
-            .map_err(|_| CacheError::LockError("Failed to acquire cache write lock".to_string()))?;
-
-        // Check if we need to enforce max entries
-        if let Some(max_entries) = self.config.max_entries {
-            if entries.len() >= max_entries {
-                return Err(CacheError::CacheFull(max_entries));
+        self.modify(|data| {
+            // Check if we need to enforce max entries
+            if let Some(max_entries) = self.config.max_entries {
+                if data.entries.len() >= max_entries {
+                    return Err(CacheError::CacheFull(max_entries));
+                }
             }
-        }
 
-        log::debug!("Inserting cache entry for path: {}", path.display());
-        entries.insert(path, entry);
-        log::debug!("Current cache size: {} entries", entries.len());
-
-        // Save without acquiring another lock
-        self.save_with_entries(&entries)?;
-        Ok(())
+            log::debug!("Inserting cache entry for path: {}", path.display());
+            data.entries.insert(path, entry);
+            log::debug!("Current cache size: {} entries", data.entries.len());
+            Ok(true)
+        })
     }
 
     pub fn validate_and_update(&self) -> CacheResult<()> {
-        let mut entries = self
-            .entries
-            .write()
-            .map_err(|_| CacheError::LockError("Failed to acquire cache write lock".to_string()))?;
-
-        // Use retain for in-place filtering
-        entries.retain(|path, entry| {
-            let is_valid = Self::validate_entry(entry, self.config.ttl_seconds);
-            if !is_valid {
-                log::debug!("Removing invalid cache entry: {}", path.display());
-            }
-            is_valid
-        });
-
-        // Save the updated entries
-        self.save_with_entries(&entries)?;
-
-        Ok(())
+        self.modify(|data| {
+            let before = data.entries.len();
+            // Use retain for in-place filtering
+            data.entries.retain(|path, entry| {
+                let is_valid = Self::validate_entry(entry, self.config.ttl_seconds);
+                if !is_valid {
+                    log::debug!("Removing invalid cache entry: {}", path.display());
+                }
+                is_valid
+            });
+            Ok(data.entries.len() != before)
+        })
     }
 
     /// Validates multiple paths in a single operation
@@ -213,16 +232,44 @@ impl Cache {
             .collect())
     }
 
+    /// Writes the in-memory entries and indexes to the cache file, on top of
+    /// whatever other processes have saved since it was loaded
     pub fn save(&self) -> CacheResult<()> {
         let entries = self
             .entries
             .read()
-            .map_err(|_| CacheError::LockError("Failed to acquire cache read lock".to_string()))?;
-
-        self.save_with_entries(&entries)
+            .map_err(|_| CacheError::LockError("Failed to acquire cache read lock".to_string()))?
+            .clone();
+        let roots = self
+            .roots
+            .read()
+            .map_err(|_| CacheError::LockError("Failed to acquire cache read lock".to_string()))?
+            .clone();
+
+        self.modify(|data| {
+            data.entries.extend(entries);
+            data.roots.extend(roots);
+            Ok(true)
+        })
     }
 
-    fn save_with_entries(&self, entries: &HashMap<PathBuf, CacheEntry>) -> CacheResult<()> {
+    /// Applies `change` to the cache file and adopts the result in memory.
+    /// The file is reloaded under an exclusive lock first, so concurrent rkit
+    /// processes, such as a background revalidation, never overwrite each
+    /// other's changes. `change` returns whether the file needs rewriting.
+    fn modify(
+        &self,
+        change: impl FnOnce(&mut LoadedCache) -> CacheResult<bool>,
+    ) -> CacheResult<()> {
+        let mut entries = self
+            .entries
+            .write()
+            .map_err(|_| CacheError::LockError("Failed to acquire cache write lock".to_string()))?;
+        let mut roots = self
+            .roots
+            .write()
+            .map_err(|_| CacheError::LockError("Failed to acquire cache write lock".to_string()))?;
+
         if let Some(parent) = self.cache_path.parent() {
             fs::create_dir_all(parent).map_err(|e| {
                 CacheError::DirectoryError(RkitError::DirectoryCreationError {
@@ -231,33 +278,38 @@ impl Cache {
                 })
             })?;
         }
+        let _lock = lock_file(&self.cache_path)?;
 
-        let cache_data = CacheData {
-            entries: entries.clone(),
-            version: 1,
+        let mut data = match load_cache(&self.cache_path) {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("Failed to reload cache, keeping the loaded state: {}", e);
+                LoadedCache {
+                    entries: entries.clone(),
+                    roots: roots.clone(),
+                }
+            }
         };
-
-        log::debug!("Saving cache to: {}", self.cache_path.display());
-        log::debug!("Cache entries to save: {}", entries.len());
-
-        // Create a temporary file for atomic write
-        let temp_path = self.cache_path.with_extension("tmp");
-        let json = serde_json::to_string(&cache_data)?;
-
-        // Write to temp file
-        if let Err(e) = fs::write(&temp_path, json) {
-            // Clean up temp file on error
-            let _ = fs::remove_file(&temp_path);
-            return Err(CacheError::IoError(e));
-        }
-
-        // Atomic rename
-        if let Err(e) = fs::rename(&temp_path, &self.cache_path) {
-            // Clean up temp file on error
-            let _ = fs::remove_file(&temp_path);
-            return Err(CacheError::IoError(e));
+        if change(&mut data)? {
+            let cache_data = CacheData {
+                entries: data.entries,
+                roots: data.roots,
+                version: CACHE_VERSION,
+            };
+
+            log::debug!("Saving cache to: {}", self.cache_path.display());
+            log::debug!("Cache entries to save: {}", cache_data.entries.len());
+
+            let json = serde_json::to_string(&cache_data)?;
+            write_atomic(&self.cache_path, &json)?;
+            data = LoadedCache {
+                entries: cache_data.entries,
+                roots: cache_data.roots,
+            };
         }
 
+        *entries = data.entries;
+        *roots = data.roots;
         Ok(())
     }
 
@@ -278,7 +330,7 @@ impl Cache {
 
         // Check if the path exists and is a git repository
         let path_exists = entry.path.exists();
-        let is_git_repo = entry.path.join(".git").exists();
+        let is_git_repo = RepoKind::detect(&entry.path).is_some();
 
         if !path_exists {
             log::debug!("Cache entry path does not exist: {}", entry.path.display());
@@ -326,17 +378,110 @@ impl Cache {
     /// Updates multiple entries and saves them to the cache
     pub fn update_and_save_many(&self, paths: &[PathBuf]) -> CacheResult<()> {
         log::debug!("Updating and saving {} cache entries", paths.len());
-        let mut entries = self
-            .entries
-            .write()
-            .map_err(|_| CacheError::LockError("Failed to acquire cache write lock".to_string()))?;
+        self.modify(|data| {
+            for path in paths {
+                let entry = Self::update_entry(path);
+                data.entries.insert(path.clone(), entry);
+            }
+            Ok(true)
+        })
+    }
+
+    /// Returns the cached repositories for a project root if its index is
+    /// still fresh and was built with the same walker settings
+    pub fn get_index(&self, root: &Path, scan_key: &str) -> Option<Vec<CacheEntry>> {
+        let entries = self.entries.read().ok()?;
+        let roots = self.roots.read().ok()?;
+        let index = roots.get(root)?;
+
+        if index.scan_key != scan_key {
+            log::debug!("Cache index for {} uses different settings", root.display());
+            return None;
+        }
+
+        if let Some(changed) = index
+            .ignore_files
+            .iter()
+            .find(|(path, mtime)| file_mtime(path) != **mtime)
+        {
+            log::debug!("Ignore file changed: {}", changed.0.display());
+            return None;
+        }
 
-        for path in paths {
-            let entry = Self::update_entry(path);
-            entries.insert(path.clone(), entry);
+        let now = get_current_time().ok()?;
+        if now.saturating_sub(index.last_scanned) > self.config.ttl_seconds {
+            log::debug!("Cache index expired for root: {}", root.display());
+            return None;
         }
 
-        self.save_with_entries(&entries)
+        Some(
+            index
+                .repos
+                .iter()
+                .filter_map(|path| entries.get(path))
+                .filter(|entry| Self::validate_entry(entry, self.config.ttl_seconds))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Seconds since the index of a project root was built, if it has one
+    /// built with the same walker settings
+    pub fn index_age(&self, root: &Path, scan_key: &str) -> Option<u64> {
+        let roots = self.roots.read().ok()?;
+        let index = roots.get(root).filter(|index| index.scan_key == scan_key)?;
+        let now = get_current_time().ok()?;
+        Some(now.saturating_sub(index.last_scanned))
+    }
+
+    /// Replaces the index for a project root with the given repositories and
+    /// refreshes their entries. The index is invalidated once one of
+    /// `ignore_files` is created, changed or removed.
+    pub fn update_index(
+        &self,
+        root: &Path,
+        scan_key: &str,
+        repos: &[PathBuf],
+        ignore_files: &[PathBuf],
+    ) -> CacheResult<()> {
+        log::debug!(
+            "Updating cache index for {} with {} repositories",
+            root.display(),
+            repos.len()
+        );
+        let index = RootIndex {
+            repos: repos.to_vec(),
+            scan_key: scan_key.to_string(),
+            last_scanned: get_current_time()?,
+            ignore_files: ignore_files
+                .iter()
+                .map(|path| (path.clone(), file_mtime(path)))
+                .collect(),
+        };
+
+        self.modify(|data| {
+            for path in repos {
+                data.entries.insert(path.clone(), Self::update_entry(path));
+            }
+            data.roots.insert(root.to_path_buf(), index);
+            Ok(true)
+        })
+    }
+
+    /// Adds a repository to the index of every project root containing it, so
+    /// that newly cloned repositories show up without a rescan
+    pub fn add_to_indexes(&self, path: &Path) -> CacheResult<()> {
+        self.modify(|data| {
+            let mut changed = false;
+            for (root, index) in data.roots.iter_mut() {
+                if path.starts_with(root) && !index.repos.iter().any(|repo| repo == path) {
+                    log::debug!("Adding {} to index of {}", path.display(), root.display());
+                    index.repos.push(path.to_path_buf());
+                    changed = true;
+                }
+            }
+            Ok(changed)
+        })
     }
 
     /// Get the TTL in seconds for cache entries
@@ -351,6 +496,17 @@ impl Default for Cache {
     }
 }
 
+/// Modification time of a file in nanoseconds since epoch, or 0 if it does
+/// not exist
+fn file_mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
 fn get_current_time() -> CacheResult<u64> {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -358,6 +514,41 @@ fn get_current_time() -> CacheResult<u64> {
         .map(|d| d.as_secs())
 }
 
+/// Opens and exclusively locks the lock file next to `path`, waiting for
+/// other processes to release it. The lock is held until the file is dropped.
+pub(crate) fn lock_file(path: &Path) -> std::io::Result<File> {
+    let lock = File::create(path.with_extension(LOCK_EXTENSION))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Writes a file through a temporary file and a rename, so readers never see
+/// a partially written file. The temporary file name is unique to the writer,
+/// so concurrent writers never write to the same temporary file.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!("{}.{}.{}.tmp", file_name, process::id(), nanos));
+
+    // Write to temp file
+    if let Err(e) = fs::write(&temp_path, contents) {
+        // Clean up temp file on error
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    // Atomic rename
+    if let Err(e) = fs::rename(&temp_path, path) {
+        // Clean up temp file on error
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
 /// Validates a cache path and ensures its parent directory exists
 fn validate_cache_path(path: &Path) -> CacheResult<()> {
     // Check if path is absolute
@@ -414,7 +605,7 @@ fn validate_cache_path(path: &Path) -> CacheResult<()> {
 }
 
 /// Gets the platform-specific cache path
-fn get_cache_path() -> CacheResult<PathBuf> {
+pub(crate) fn get_cache_path() -> CacheResult<PathBuf> {
     let config_dir = if cfg!(windows) {
         dirs::config_dir().ok_or_else(|| {
             CacheError::DirectoryError(RkitError::ConfigError(
@@ -439,14 +630,18 @@ fn get_cache_path() -> CacheResult<PathBuf> {
     Ok(cache_path)
 }
 
-fn load_cache(cache_path: &Path) -> CacheResult<HashMap<PathBuf, CacheEntry>> {
+fn load_cache(cache_path: &Path) -> CacheResult<LoadedCache> {
     if !cache_path.exists() {
-        return Ok(HashMap::new());
+        return Ok(LoadedCache::default());
     }
 
     let contents = fs::read_to_string(cache_path)?;
     match serde_json::from_str::<CacheData>(&contents) {
-        Ok(data) if data.version == 1 => Ok(data.entries),
+        // Version 1 caches have no root index and are upgraded on next save
+        Ok(data) if data.version == 1 || data.version == CACHE_VERSION => Ok(LoadedCache {
+            entries: data.entries,
+            roots: data.roots,
+        }),
         Ok(data) => {
             log::warn!("Invalid cache version: {}", data.version);
             Err(CacheError::InvalidVersion(data.version))
@@ -457,3 +652,153 @@ fn load_cache(cache_path: &Path) -> CacheResult<HashMap<PathBuf, CacheEntry>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn test_cache(dir: &Path) -> Cache {
+        Cache::with_config(CacheConfig {
+            cache_path: Some(dir.join("cache.json")),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let repo = root.join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        let cache = test_cache(dir.path());
+        assert!(cache.get_index(&root, "key").is_none());
+
+        cache
+            .update_index(&root, "key", std::slice::from_ref(&repo), &[])
+            .unwrap();
+        let entries = cache.get_index(&root, "key").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, repo);
+
+        // The index survives a reload from disk
+        let reloaded = test_cache(dir.path());
+        assert_eq!(reloaded.get_index(&root, "key").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_index_updates() {
+        let dir = tempdir().unwrap();
+        let roots: Vec<PathBuf> = (0..8).map(|i| dir.path().join(i.to_string())).collect();
+        for root in &roots {
+            fs::create_dir_all(root.join("repo").join(".git")).unwrap();
+        }
+
+        // Each cache stands in for a separate rkit process, all loaded before
+        // any of them writes
+        let caches: Vec<Cache> = roots.iter().map(|_| test_cache(dir.path())).collect();
+        std::thread::scope(|scope| {
+            for (cache, root) in caches.iter().zip(&roots) {
+                scope.spawn(move || {
+                    cache
+                        .update_index(root, "key", &[root.join("repo")], &[])
+                        .unwrap()
+                });
+            }
+        });
+
+        let reloaded = test_cache(dir.path());
+        for root in &roots {
+            assert!(
+                reloaded.get_index(root, "key").is_some(),
+                "{}",
+                root.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_to_indexes() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let repo = root.join("repo");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(outside.join(".git")).unwrap();
+
+        let cache = test_cache(dir.path());
+        cache.update_index(&root, "key", &[], &[]).unwrap();
+        cache.update_and_save(&repo).unwrap();
+        cache.add_to_indexes(&repo).unwrap();
+        cache.add_to_indexes(&outside).unwrap();
+
+        let entries = cache.get_index(&root, "key").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, repo);
+    }
+
+    #[test]
+    fn test_index_scan_key_mismatch() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let cache = test_cache(dir.path());
+
+        cache.update_index(&root, "key", &[], &[]).unwrap();
+        assert!(cache.get_index(&root, "key").is_some());
+        assert!(cache.get_index(&root, "other").is_none());
+    }
+
+    #[test]
+    fn test_index_ignore_file_changes() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let nested = root.join("org").join(".rkitignore");
+        fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        fs::write(&nested, "old/\n").unwrap();
+        let ignore_files = [root.join(".rkitignore"), nested.clone()];
+
+        let cache = test_cache(dir.path());
+        cache
+            .update_index(&root, "key", &[], &ignore_files)
+            .unwrap();
+        assert!(cache.get_index(&root, "key").is_some());
+
+        // Creating the root ignore file invalidates the index
+        fs::write(&ignore_files[0], "archive/\n").unwrap();
+        assert!(cache.get_index(&root, "key").is_none());
+
+        cache
+            .update_index(&root, "key", &[], &ignore_files)
+            .unwrap();
+        fs::remove_file(&nested).unwrap();
+        assert!(cache.get_index(&root, "key").is_none());
+    }
+
+    #[test]
+    fn test_index_drops_removed_repos() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let repo = root.join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        let cache = test_cache(dir.path());
+        cache
+            .update_index(&root, "key", std::slice::from_ref(&repo), &[])
+            .unwrap();
+        fs::remove_dir_all(&repo).unwrap();
+
+        assert!(cache.get_index(&root, "key").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_version_1_cache() {
+        let dir = tempdir().unwrap();
+        let cache_path = dir.path().join("cache.json");
+        fs::write(&cache_path, r#"{"entries":{},"version":1}"#).unwrap();
+
+        let loaded = load_cache(&cache_path).unwrap();
+        assert!(loaded.entries.is_empty());
+        assert!(loaded.roots.is_empty());
+    }
+}
@@ -1,119 +1,232 @@
+use crate::commands::fetch::{update_repo, UpdateMode, UpdateOutcome};
+use crate::config::{CloneFlags, Config};
 use crate::error::{RkitError, RkitResult};
+use crate::git::origin_url;
+use crate::layout::Layouts;
+use crate::process::output_with_timeout;
+use crate::repo_url::{parse_repo_url, ParsedRepoUrl};
+use crate::usage::{record_visit, Usage};
+use crate::workspace::{ProjectRoot, Workspace};
 use crate::CACHE;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use url::Url;
 
-#[derive(Debug)]
-pub struct ParsedRepoUrl {
-    pub domain: String,
-    pub org: String,
-    pub repo: String,
+/// What `clone` does when the target directory exists but is not a clone of
+/// the requested repository
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExistingStrategy {
+    /// Fail with `TargetExistsError`
+    #[default]
+    Fail,
+    /// Delete the directory and clone in its place
+    Force,
+    /// Clone next to it, into the first free `<repo>-<n>` directory
+    Suffix,
 }
 
-fn trim_git_suffix(repo: &str) -> &str {
-    repo.trim_end_matches(".git")
+/// Options for `clone`
+#[derive(Debug, Clone, Default)]
+pub struct CloneOptions {
+    /// Layouts deciding where the repository is placed under the project root
+    pub layouts: Layouts,
+    /// Flags passed to `git clone`, taking precedence over `domain_flags`
+    pub flags: CloneFlags,
+    /// Default flags per domain, keyed by lowercase host name
+    pub domain_flags: HashMap<String, CloneFlags>,
+    /// How to handle a target directory that holds something else
+    pub existing: ExistingStrategy,
+    /// Fetch the repository if it is already cloned
+    pub fetch_existing: bool,
+    /// Root to clone into, overriding `domain_roots`
+    pub root: Option<String>,
+    /// Root to clone into per domain, keyed by lowercase host name
+    pub domain_roots: HashMap<String, String>,
+    /// Usage store the clone is recorded in as a visit, if any
+    pub usage_file: Option<PathBuf>,
 }
 
-fn parse_https_url(url: &str) -> RkitResult<ParsedRepoUrl> {
-    let parsed_url = Url::parse(url).map_err(|e| {
-        log::error!("Failed to parse HTTPS URL: {}", e);
-        RkitError::InvalidRepoUrl(format!("Failed to parse HTTPS URL: {}", e))
-    })?;
+impl CloneOptions {
+    /// Options with the layouts and per-domain clone defaults from `config`
+    pub fn from_config(config: &Config) -> RkitResult<Self> {
+        let domain_flags = config
+            .domains
+            .iter()
+            .filter_map(|(domain, settings)| {
+                let flags = settings.clone.clone()?;
+                Some((domain.to_lowercase(), flags))
+            })
+            .collect();
+        let domain_roots = config
+            .domains
+            .iter()
+            .filter_map(|(domain, settings)| {
+                let root = settings.root.clone()?;
+                Some((domain.to_lowercase(), root))
+            })
+            .collect();
+
+        Ok(Self {
+            layouts: Layouts::from_config(config)?,
+            domain_flags,
+            domain_roots,
+            usage_file: Some(Usage::default_path()),
+            ..Default::default()
+        })
+    }
 
-    let domain = parsed_url.host_str().ok_or_else(|| {
-        log::error!("No domain found in HTTPS URL: {}", url);
-        RkitError::InvalidRepoUrl("No domain found in HTTPS URL".to_string())
-    })?;
+    /// Root that repositories from `domain` are cloned into: the explicitly
+    /// selected root, then the domain's rule, then the workspace's first root
+    pub fn root_for<'a>(
+        &self,
+        workspace: &'a Workspace,
+        domain: &str,
+    ) -> RkitResult<&'a ProjectRoot> {
+        match self
+            .root
+            .as_ref()
+            .or_else(|| self.domain_roots.get(&domain.to_lowercase()))
+        {
+            Some(name) => workspace.root(name),
+            None => Ok(workspace.default_root()),
+        }
+    }
 
-    let path_segments: Vec<&str> = parsed_url
-        .path_segments()
-        .ok_or_else(|| {
-            log::error!("No path segments in HTTPS URL: {}", url);
-            RkitError::InvalidRepoUrl("No path segments in HTTPS URL".to_string())
-        })?
-        .collect();
-
-    if path_segments.len() < 2 {
-        log::error!(
-            "HTTPS URL must contain organization and repository: {}",
-            url
-        );
-        return Err(RkitError::InvalidRepoUrl(
-            "HTTPS URL must contain organization and repository".to_string(),
-        ));
+    fn record_visit(&self, repo: &Path) {
+        if let Some(store) = &self.usage_file {
+            record_visit(store, repo);
+        }
     }
 
-    Ok(ParsedRepoUrl {
-        domain: domain.to_string(),
-        org: path_segments[0].to_string(),
-        repo: trim_git_suffix(path_segments[1]).to_string(),
-    })
+    /// Flags used when cloning from `domain`
+    pub fn flags_for(&self, domain: &str) -> CloneFlags {
+        match self.domain_flags.get(&domain.to_lowercase()) {
+            Some(defaults) => self.flags.or(defaults),
+            None => self.flags.clone(),
+        }
+    }
 }
 
-fn parse_ssh_url(url: &str) -> RkitResult<ParsedRepoUrl> {
-    // Handle potential port number in domain
-    let (domain, path) = if let Some(idx) = url.rfind(':') {
-        let domain_part = &url[url.find('@').ok_or_else(|| {
-            log::error!("Invalid SSH URL format (no @ symbol): {}", url);
-            RkitError::InvalidRepoUrl("Invalid SSH URL format (no @ symbol)".to_string())
-        })? + 1..idx];
-
-        // Remove port number if present
-        let domain = domain_part.split(':').next().ok_or_else(|| {
-            log::error!("No domain found in SSH URL: {}", url);
-            RkitError::InvalidRepoUrl("No domain found in SSH URL".to_string())
-        })?;
-
-        (domain, &url[idx + 1..])
-    } else {
-        log::error!("Invalid SSH URL format (no path separator): {}", url);
-        return Err(RkitError::InvalidRepoUrl(
-            "Invalid SSH URL format (no path separator)".to_string(),
-        ));
+/// Contents of a clone target that already exists
+#[derive(Debug, PartialEq, Eq)]
+enum ExistingTarget {
+    /// A clone of the requested repository
+    SameRepo,
+    /// Anything else, with a description for error messages
+    Conflict(String),
+}
+
+/// Inspects a clone target, returning `None` if it is missing or an empty
+/// directory that git can clone into
+fn inspect_target(target_dir: &Path, parsed: &ParsedRepoUrl) -> Option<ExistingTarget> {
+    let mut entries = match target_dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) if !target_dir.exists() => return None,
+        Err(_) => return Some(ExistingTarget::Conflict("not a directory".to_string())),
     };
+    // An empty directory is fine to clone into
+    let _ = entries.next()?;
 
-    let path_parts: Vec<&str> = path.split('/').collect();
-    if path_parts.len() != 2 {
-        log::error!("SSH URL must contain organization and repository: {}", url);
-        return Err(RkitError::InvalidRepoUrl(
-            "SSH URL must contain organization and repository".to_string(),
-        ));
+    if !target_dir.join(".git").exists() && !target_dir.join("HEAD").is_file() {
+        return Some(ExistingTarget::Conflict("not a git repository".to_string()));
     }
 
-    Ok(ParsedRepoUrl {
-        domain: domain.to_string(),
-        org: path_parts[0].to_string(),
-        repo: trim_git_suffix(path_parts[1]).to_string(),
-    })
-}
-
-pub fn parse_repo_url(url: &str) -> RkitResult<ParsedRepoUrl> {
-    // Try parsing as HTTPS URL first
-    if url.starts_with("http://") || url.starts_with("https://") {
-        return parse_https_url(url);
+    let existing = match origin_url(target_dir) {
+        Some(url) => url,
+        None => return Some(ExistingTarget::Conflict("no origin remote".to_string())),
+    };
+    match parse_repo_url(&existing) {
+        Ok(existing_parsed) if existing_parsed.same_repo(parsed) => Some(ExistingTarget::SameRepo),
+        _ => Some(ExistingTarget::Conflict(format!("origin is {}", existing))),
     }
+}
 
-    // Try parsing as SSH URL
-    if url.contains('@') {
-        return parse_ssh_url(url);
+/// Reports an existing clone of the requested repository, fetching it if
+/// requested
+fn use_existing(target_dir: &Path, options: &CloneOptions) -> RkitResult<()> {
+    log::info!("Already cloned: {}", target_dir.display());
+    if options.fetch_existing {
+        if let UpdateOutcome::Failed(e) = update_repo(target_dir, UpdateMode::Fetch, None).outcome {
+            log::error!("Failed to fetch {}: {}", target_dir.display(), e);
+            return Err(e);
+        }
     }
-
-    Err(RkitError::InvalidRepoUrl(
-        "URL must be either HTTPS or SSH format".to_string(),
-    ))
+    options.record_visit(target_dir);
+    println!("{}", target_dir.display());
+    Ok(())
 }
 
-pub fn clone(url: &str, project_root: &Path) -> RkitResult<()> {
+/// Clones a repository into its project root following the configured
+/// layout, printing the path of the clone. A target that already holds a
+/// clone of the same repository is reused.
+pub fn clone(url: &str, workspace: &Workspace, options: &CloneOptions) -> RkitResult<()> {
     log::info!("Cloning repository: {}", url);
 
     let parsed_url = parse_repo_url(url)?;
+    let root = options.root_for(workspace, &parsed_url.domain)?;
+    let mut target_dir = root.path.join(options.layouts.render(&parsed_url));
+
+    match inspect_target(&target_dir, &parsed_url) {
+        None => {}
+        Some(ExistingTarget::SameRepo) => return use_existing(&target_dir, options),
+        Some(ExistingTarget::Conflict(reason)) => match options.existing {
+            ExistingStrategy::Fail => {
+                log::error!(
+                    "Target directory {} already exists: {}",
+                    target_dir.display(),
+                    reason
+                );
+                return Err(RkitError::TargetExistsError {
+                    path: target_dir,
+                    reason,
+                });
+            }
+            ExistingStrategy::Force => {
+                log::warn!("Removing {} ({})", target_dir.display(), reason);
+                let removed = if target_dir.is_dir() {
+                    std::fs::remove_dir_all(&target_dir)
+                } else {
+                    std::fs::remove_file(&target_dir)
+                };
+                if let Err(e) = removed {
+                    log::error!("Failed to remove {}: {}", target_dir.display(), e);
+                    return Err(RkitError::IoError(e));
+                }
+            }
+            ExistingStrategy::Suffix => {
+                let base = target_dir.clone();
+                for n in 2.. {
+                    let mut name = base.file_name().unwrap_or_default().to_os_string();
+                    name.push(format!("-{}", n));
+                    target_dir = base.with_file_name(name);
+                    match inspect_target(&target_dir, &parsed_url) {
+                        None => break,
+                        Some(ExistingTarget::SameRepo) => {
+                            return use_existing(&target_dir, options)
+                        }
+                        Some(ExistingTarget::Conflict(_)) => continue,
+                    }
+                }
+                log::info!("Target exists, cloning into {}", target_dir.display());
+            }
+        },
+    }
 
-    let target_dir = project_root
-        .join(&parsed_url.domain)
-        .join(&parsed_url.org)
-        .join(&parsed_url.repo);
+    clone_into(
+        url,
+        &target_dir,
+        &options.flags_for(&parsed_url.domain),
+        false,
+    )?;
+    options.record_visit(&target_dir);
+    println!("{}", target_dir.display());
+    Ok(())
+}
 
+/// Clones `url` into `target_dir` and adds it to the cache. With `quiet`,
+/// git's output is captured instead of being shown, so several clones can
+/// run side by side.
+pub fn clone_into(url: &str, target_dir: &Path, flags: &CloneFlags, quiet: bool) -> RkitResult<()> {
     if let Some(parent) = target_dir.parent() {
         if !parent.exists() {
             log::debug!("Creating parent directories: {}", parent.display());
@@ -130,27 +243,46 @@ pub fn clone(url: &str, project_root: &Path) -> RkitResult<()> {
         }
     }
 
-    log::info!("Running: git clone {} {}", url, target_dir.display());
-    let status = Command::new("git")
-        .arg("clone")
-        .arg(url)
-        .arg(&target_dir)
-        .status()
-        .map_err(|e| RkitError::ShellCommandError {
-            command: format!("git clone {} {}", url, target_dir.display()),
-            source: e,
-        })?;
+    let flags = flags.to_args();
+    let mut command_parts = vec!["git".to_string(), "clone".to_string()];
+    command_parts.extend(flags.iter().cloned());
+    command_parts.push(url.to_string());
+    command_parts.push(target_dir.display().to_string());
+    let command_str = command_parts.join(" ");
+    log::info!("Running: {}", command_str);
+
+    let mut command = Command::new("git");
+    command.arg("clone").args(&flags).arg(url).arg(target_dir);
+    let result = if quiet {
+        // Never block a parallel clone on a credential prompt
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        output_with_timeout(&mut command, None).map(|output| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            (
+                output.success(),
+                stderr.trim().lines().last().unwrap_or_default().to_string(),
+            )
+        })
+    } else {
+        command
+            .status()
+            .map(|status| (status.success(), status.to_string()))
+    };
+    let (success, detail) = result.map_err(|e| RkitError::ShellCommandError {
+        command: command_str,
+        source: e,
+    })?;
 
-    if !status.success() {
-        log::error!("git clone failed with status: {}", status);
-        return Err(RkitError::GitError(format!(
-            "git clone failed with status: {}",
-            status
-        )));
+    if !success {
+        log::error!("git clone of {} failed: {}", url, detail);
+        return Err(RkitError::GitError(format!("git clone failed: {}", detail)));
     }
 
     // Cache the newly cloned repository
-    if let Err(e) = CACHE.update_and_save(&target_dir) {
+    if let Err(e) = CACHE
+        .update_and_save(target_dir)
+        .and_then(|_| CACHE.add_to_indexes(target_dir))
+    {
         log::warn!("Failed to cache cloned repository: {}", e);
     }
 
@@ -161,95 +293,170 @@ pub fn clone(url: &str, project_root: &Path) -> RkitResult<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::repo_url::LOCAL_DOMAIN;
+    use crate::test_support::bare_remote;
     use tempfile::tempdir;
 
     #[test]
-    fn test_parse_repo_url_https() {
-        let url = "https://github.com/org/repo.git";
-        let parsed = parse_repo_url(url).unwrap();
-        assert_eq!(parsed.domain, "github.com");
-        assert_eq!(parsed.org, "org");
-        assert_eq!(parsed.repo, "repo");
-    }
-
-    #[test]
-    fn test_parse_repo_url_https_without_git_suffix() {
-        let url = "https://github.com/org/repo";
-        let parsed = parse_repo_url(url).unwrap();
-        assert_eq!(parsed.domain, "github.com");
-        assert_eq!(parsed.org, "org");
-        assert_eq!(parsed.repo, "repo");
+    #[cfg(unix)]
+    fn test_clone_local_bare_repo() {
+        let dir = tempdir().unwrap();
+        let remote = bare_remote(dir.path());
+
+        let root = dir.path().join("projects");
+        let url = format!("file://{}", remote.display());
+        clone(
+            &url,
+            &Workspace::single(&root, None),
+            &CloneOptions::default(),
+        )
+        .unwrap();
+        assert!(root
+            .join(LOCAL_DOMAIN)
+            .join("org")
+            .join("repo")
+            .join(".git")
+            .exists());
     }
 
     #[test]
-    fn test_parse_repo_url_ssh() {
-        let url = "git@github.com:org/repo.git";
-        let parsed = parse_repo_url(url).unwrap();
-        assert_eq!(parsed.domain, "github.com");
-        assert_eq!(parsed.org, "org");
-        assert_eq!(parsed.repo, "repo");
+    #[cfg(unix)]
+    fn test_clone_with_domain_flags() {
+        let dir = tempdir().unwrap();
+        let remote = bare_remote(dir.path());
+
+        let mut options = CloneOptions::default();
+        options.domain_flags.insert(
+            LOCAL_DOMAIN.to_string(),
+            CloneFlags {
+                bare: true,
+                ..Default::default()
+            },
+        );
+        let root = dir.path().join("projects");
+        clone(
+            &format!("file://{}", remote.display()),
+            &Workspace::single(&root, None),
+            &options,
+        )
+        .unwrap();
+
+        let target = root.join(LOCAL_DOMAIN).join("org").join("repo");
+        assert!(target.join("HEAD").exists());
+        assert!(!target.join(".git").exists());
     }
 
     #[test]
-    fn test_parse_repo_url_ssh_without_git_suffix() {
-        let url = "git@github.com:org/repo";
-        let parsed = parse_repo_url(url).unwrap();
-        assert_eq!(parsed.domain, "github.com");
-        assert_eq!(parsed.org, "org");
-        assert_eq!(parsed.repo, "repo");
+    #[cfg(unix)]
+    fn test_clone_existing_target() {
+        let dir = tempdir().unwrap();
+        let remote = bare_remote(dir.path());
+
+        let root = dir.path().join("projects");
+        let url = format!("file://{}", remote.display());
+        let target = root.join(LOCAL_DOMAIN).join("org").join("repo");
+        let workspace = Workspace::single(&root, None);
+        let usage_file = dir.path().join("usage.json");
+        let mut options = CloneOptions {
+            fetch_existing: true,
+            usage_file: Some(usage_file.clone()),
+            ..Default::default()
+        };
+
+        // Cloning the same repository again reuses the existing clone, and
+        // both count as a visit
+        clone(&url, &workspace, &options).unwrap();
+        clone(&url, &workspace, &options).unwrap();
+        assert_eq!(Usage::load(&usage_file).get(&target).unwrap().rank, 2.0);
+
+        // A directory holding something else is a conflict
+        std::fs::remove_dir_all(&target).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("notes.txt"), "keep").unwrap();
+        let result = clone(&url, &workspace, &options);
+        assert!(matches!(result, Err(RkitError::TargetExistsError { .. })));
+
+        options.existing = ExistingStrategy::Suffix;
+        clone(&url, &workspace, &options).unwrap();
+        assert!(target.join("notes.txt").exists());
+        assert!(target.with_file_name("repo-2").join(".git").exists());
+        // The suffixed clone is found again instead of creating repo-3
+        clone(&url, &workspace, &options).unwrap();
+        assert!(!target.with_file_name("repo-3").exists());
+
+        options.existing = ExistingStrategy::Force;
+        clone(&url, &workspace, &options).unwrap();
+        assert!(!target.join("notes.txt").exists());
+        assert!(target.join(".git").exists());
     }
 
     #[test]
-    fn test_parse_repo_url_invalid() {
-        let url = "not_a_url";
-        let parsed = parse_repo_url(url);
-        assert!(parsed.is_err());
+    fn test_clone_root_selection() {
+        let workspace = Workspace::new(vec![
+            ProjectRoot::new("work", Path::new("/nvme/work"), Default::default()),
+            ProjectRoot::new("personal", Path::new("/data/personal"), Default::default()),
+        ])
+        .unwrap();
+        let mut options = CloneOptions::default();
+        options
+            .domain_roots
+            .insert("github.com".to_string(), "personal".to_string());
+
+        let root = |options: &CloneOptions, domain: &str| {
+            options.root_for(&workspace, domain).map(|r| r.name.clone())
+        };
+        assert_eq!(root(&options, "GitHub.com").unwrap(), "personal");
+        assert_eq!(root(&options, "git.example.com").unwrap(), "work");
+
+        options.root = Some("work".to_string());
+        assert_eq!(root(&options, "github.com").unwrap(), "work");
+
+        options.root = Some("missing".to_string());
+        assert!(matches!(
+            root(&options, "github.com"),
+            Err(RkitError::ConfigError(_))
+        ));
     }
 
     #[test]
-    fn test_parse_repo_url_https_incomplete() {
-        let url = "https://github.com/org";
-        let parsed = parse_repo_url(url);
-        assert!(parsed.is_err());
-    }
+    fn test_clone_flags_precedence() {
+        let mut options = CloneOptions {
+            flags: CloneFlags {
+                depth: Some(1),
+                mirror: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        options.domain_flags.insert(
+            "git.example.com".to_string(),
+            CloneFlags {
+                depth: Some(50),
+                filter: Some("blob:none".to_string()),
+                bare: true,
+                ..Default::default()
+            },
+        );
 
-    #[test]
-    fn test_parse_repo_url_ssh_incomplete() {
-        let url = "git@github.com:org";
-        let parsed = parse_repo_url(url);
-        assert!(parsed.is_err());
+        assert_eq!(
+            options.flags_for("Git.Example.com").to_args(),
+            vec!["--depth=1", "--filter=blob:none", "--mirror"]
+        );
+        assert_eq!(
+            options.flags_for("github.com").to_args(),
+            vec!["--depth=1", "--mirror"]
+        );
     }
 
     #[test]
     fn test_clone_invalid_url() {
         let dir = tempdir().unwrap();
         let url = "not_a_url";
-        let result = clone(url, dir.path());
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_trim_git_suffix() {
-        assert_eq!(trim_git_suffix("repo.git"), "repo");
-        assert_eq!(trim_git_suffix("repo"), "repo");
-        assert_eq!(trim_git_suffix("my-repo.git"), "my-repo");
-    }
-
-    #[test]
-    fn test_target_directory_construction() {
-        let dir = tempdir().unwrap();
-        let url = "https://github.com/org/repo.git";
-        let parsed = parse_repo_url(url).unwrap();
-
-        let expected_target = dir
-            .path()
-            .join(&parsed.domain)
-            .join(&parsed.org)
-            .join(&parsed.repo);
-
-        assert_eq!(
-            expected_target,
-            dir.path().join("github.com").join("org").join("repo")
+        let result = clone(
+            url,
+            &Workspace::single(dir.path(), None),
+            &CloneOptions::default(),
         );
+        assert!(result.is_err());
     }
 }
@@ -0,0 +1,220 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{RkitError, RkitResult};
+use crate::filter::RepoFilter;
+use crate::process::{output_with_timeout, run_prefixed};
+use crate::workers::run_parallel;
+use crate::workspace::Workspace;
+
+/// Options for `exec_repos`
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    /// Program and arguments to run in every repository
+    pub command: Vec<String>,
+    /// Run in one repository at a time instead of in parallel
+    pub serial: bool,
+    /// Buffer each repository's output and print it as one block instead of
+    /// streaming prefixed lines
+    pub group: bool,
+    /// Label output with absolute paths instead of relative paths
+    pub full: bool,
+    /// Ignore the cached index and rescan the project root
+    pub refresh: bool,
+    /// Restricts which repositories the command runs in
+    pub filter: RepoFilter,
+}
+
+/// Result of running the command in a single repository
+#[derive(Debug, Clone)]
+pub struct ExecResult {
+    pub path: PathBuf,
+    /// Exit code, or `None` if the command was killed by a signal or could
+    /// not be started
+    pub exit_code: Option<i32>,
+    /// Error starting the command, if any
+    pub error: Option<String>,
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// Runs `command` with `repo_path` as its working directory
+pub fn exec_in_repo(repo_path: &Path, label: &str, command: &[String], group: bool) -> ExecResult {
+    let mut result = ExecResult {
+        path: repo_path.to_path_buf(),
+        exit_code: None,
+        error: None,
+    };
+
+    let Some((program, args)) = command.split_first() else {
+        result.error = Some("No command given".to_string());
+        return result;
+    };
+
+    let mut child = Command::new(program);
+    child.args(args).current_dir(repo_path);
+    log::debug!("Running in {}: {}", repo_path.display(), command.join(" "));
+
+    if group {
+        match output_with_timeout(&mut child, None) {
+            Ok(output) => {
+                {
+                    let mut stdout = io::stdout().lock();
+                    let _ = writeln!(stdout, "=== {} ===", label);
+                    let _ = stdout.write_all(&output.stdout);
+                    let _ = stdout.flush();
+                }
+                if !output.stderr.is_empty() {
+                    let _ = io::stderr().lock().write_all(&output.stderr);
+                }
+                result.exit_code = output.status.and_then(|s| s.code());
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+    } else {
+        match run_prefixed(&mut child, &format!("{}: ", label)) {
+            Ok(status) => result.exit_code = status.code(),
+            Err(e) => result.error = Some(e.to_string()),
+        }
+    }
+
+    if let Some(error) = &result.error {
+        log::error!("Failed to run '{}' in {}: {}", program, label, error);
+    }
+    result
+}
+
+pub fn exec_repos(workspace: &Workspace, options: &ExecOptions) -> RkitResult<()> {
+    if options.command.is_empty() {
+        return Err(RkitError::ConfigError(
+            "No command given to run in repositories".to_string(),
+        ));
+    }
+
+    let threads = if options.serial {
+        1
+    } else {
+        workspace.threads()
+    };
+    let repos: Vec<PathBuf> = workspace
+        .find_repos(options.refresh)
+        .into_iter()
+        .filter(|repo| options.filter.matches_repo(repo, workspace.relative(repo)))
+        .collect();
+    log::info!(
+        "Running '{}' in {} repositories",
+        options.command.join(" "),
+        repos.len()
+    );
+
+    let label = |path: &Path| workspace.display(path, options.full);
+
+    let results = run_parallel(&repos, threads, |repo| {
+        exec_in_repo(repo, &label(repo), &options.command, options.group)
+    });
+
+    let failed: Vec<&ExecResult> = results.iter().filter(|r| !r.success()).collect();
+    eprintln!();
+    eprintln!(
+        "Ran in {} repositories: {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed.len(),
+        failed.len()
+    );
+    for result in &failed {
+        match (&result.error, result.exit_code) {
+            (Some(error), _) => eprintln!("  {}: {}", label(&result.path), error),
+            (None, Some(code)) => eprintln!("  {}: exit code {}", label(&result.path), code),
+            (None, None) => eprintln!("  {}: terminated by signal", label(&result.path)),
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(RkitError::ExecError {
+            failed: failed.len(),
+            total: results.len(),
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exec_in_repo_working_directory() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+
+        let result = exec_in_repo(&repo, "repo", &command(&["touch", "marker"]), false);
+        assert!(result.success());
+        assert!(repo.join("marker").exists());
+    }
+
+    #[test]
+    fn test_exec_in_repo_exit_code() {
+        let dir = tempdir().unwrap();
+        let result = exec_in_repo(dir.path(), "repo", &command(&["sh", "-c", "exit 4"]), true);
+        assert!(!result.success());
+        assert_eq!(result.exit_code, Some(4));
+    }
+
+    #[test]
+    fn test_exec_in_repo_missing_program() {
+        let dir = tempdir().unwrap();
+        let result = exec_in_repo(
+            dir.path(),
+            "repo",
+            &command(&["rkit-definitely-missing-program"]),
+            false,
+        );
+        assert!(!result.success());
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn test_exec_repos_filter_and_summary() {
+        let dir = tempdir().unwrap();
+        let included = dir.path().join("github.com").join("org").join("a");
+        let excluded = dir.path().join("gitlab.com").join("org").join("b");
+        fs::create_dir_all(included.join(".git")).unwrap();
+        fs::create_dir_all(excluded.join(".git")).unwrap();
+
+        let options = ExecOptions {
+            command: command(&["touch", "marker"]),
+            refresh: true,
+            filter: RepoFilter::new().domains(vec!["github.com".to_string()]),
+            ..Default::default()
+        };
+        exec_repos(&Workspace::single(dir.path(), None), &options).unwrap();
+        assert!(included.join("marker").exists());
+        assert!(!excluded.join("marker").exists());
+
+        let options = ExecOptions {
+            command: command(&["false"]),
+            ..options
+        };
+        let result = exec_repos(&Workspace::single(dir.path(), None), &options);
+        assert!(matches!(
+            result,
+            Err(RkitError::ExecError {
+                failed: 1,
+                total: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,389 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::error::{RkitError, RkitResult};
+use crate::process::output_with_timeout;
+use crate::scanner::RepoKind;
+use crate::workers::run_parallel;
+use crate::workspace::Workspace;
+
+/// Git operation run by `update_repos`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// `git fetch`
+    Fetch,
+    /// `git pull --ff-only`
+    Pull,
+}
+
+impl UpdateMode {
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            UpdateMode::Fetch => &["fetch"],
+            UpdateMode::Pull => &["pull", "--ff-only"],
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            UpdateMode::Fetch => "Fetched",
+            UpdateMode::Pull => "Pulled",
+        }
+    }
+}
+
+/// Options for `update_workspace`
+#[derive(Debug, Clone)]
+pub struct UpdateOptions {
+    pub mode: UpdateMode,
+    /// Maximum time a single repository may take before it is killed
+    pub timeout: Option<Duration>,
+    /// Print absolute paths instead of paths relative to the project root
+    pub full: bool,
+    /// Ignore the cached index and rescan the project root
+    pub refresh: bool,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        Self {
+            mode: UpdateMode::Fetch,
+            timeout: Some(Duration::from_secs(120)),
+            full: false,
+            refresh: false,
+        }
+    }
+}
+
+/// Outcome of fetching or pulling a single repository
+#[derive(Debug)]
+pub enum UpdateOutcome {
+    Updated,
+    /// The local branch has diverged from its upstream
+    NotFastForward,
+    /// A bare repository, which has no working tree to pull into
+    Skipped,
+    Failed(RkitError),
+}
+
+/// Result of updating a single repository
+#[derive(Debug)]
+pub struct RepoUpdate {
+    pub path: PathBuf,
+    pub outcome: UpdateOutcome,
+    pub duration: Duration,
+}
+
+/// Whether `ancestor` is reachable from `descendant`, or `None` if git could
+/// not tell, e.g. because the branch has no upstream
+fn is_ancestor(repo_path: &Path, ancestor: &str, descendant: &str) -> Option<bool> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["merge-base", "--is-ancestor", ancestor, descendant])
+        .output()
+        .ok()?;
+    // Exit code 1 means not an ancestor; anything else is an error
+    match output.status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether HEAD and its upstream both have commits the other lacks, so that
+/// a pull cannot fast-forward. Checked through the commit graph rather than
+/// git's messages, which are translated.
+fn has_diverged(repo_path: &Path) -> bool {
+    is_ancestor(repo_path, "HEAD", "@{u}") == Some(false)
+        && is_ancestor(repo_path, "@{u}", "HEAD") == Some(false)
+}
+
+/// Fetches or fast-forward pulls a single repository. Bare repositories are
+/// skipped when pulling.
+pub fn update_repo(repo_path: &Path, mode: UpdateMode, timeout: Option<Duration>) -> RepoUpdate {
+    if mode == UpdateMode::Pull && RepoKind::detect(repo_path) == Some(RepoKind::Bare) {
+        log::debug!("Skipping bare repository {}", repo_path.display());
+        return RepoUpdate {
+            path: repo_path.to_path_buf(),
+            outcome: UpdateOutcome::Skipped,
+            duration: Duration::ZERO,
+        };
+    }
+
+    let args = mode.args();
+    let command_str = format!("git -C {} {}", repo_path.display(), args.join(" "));
+    log::debug!("Running: {}", command_str);
+
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        // Never block a worker on a credential prompt
+        .env("GIT_TERMINAL_PROMPT", "0");
+
+    let (outcome, duration) = match output_with_timeout(&mut command, timeout) {
+        Ok(output) if output.timed_out => (
+            UpdateOutcome::Failed(RkitError::GitError(format!(
+                "{} timed out after {:?}",
+                command_str, output.duration
+            ))),
+            output.duration,
+        ),
+        Ok(output) if output.success() => (UpdateOutcome::Updated, output.duration),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let outcome = if mode == UpdateMode::Pull && has_diverged(repo_path) {
+                UpdateOutcome::NotFastForward
+            } else {
+                UpdateOutcome::Failed(RkitError::GitError(format!(
+                    "{} failed: {}",
+                    command_str,
+                    stderr.lines().last().unwrap_or("unknown error")
+                )))
+            };
+            (outcome, output.duration)
+        }
+        Err(e) => (
+            UpdateOutcome::Failed(RkitError::ShellCommandError {
+                command: command_str,
+                source: e,
+            }),
+            Duration::ZERO,
+        ),
+    };
+
+    RepoUpdate {
+        path: repo_path.to_path_buf(),
+        outcome,
+        duration,
+    }
+}
+
+/// Updates every repository on a bounded pool of worker threads, calling
+/// `on_done` as each one finishes
+pub fn update_repos<F>(
+    repos: &[PathBuf],
+    mode: UpdateMode,
+    timeout: Option<Duration>,
+    threads: usize,
+    on_done: F,
+) -> Vec<RepoUpdate>
+where
+    F: Fn(&RepoUpdate) + Sync,
+{
+    run_parallel(repos, threads, |repo| {
+        let update = update_repo(repo, mode, timeout);
+        on_done(&update);
+        update
+    })
+}
+
+pub fn update_workspace(workspace: &Workspace, options: &UpdateOptions) -> RkitResult<()> {
+    let threads = workspace.threads();
+    let repos = workspace.find_repos(options.refresh);
+    log::info!(
+        "Updating {} repositories with {} workers",
+        repos.len(),
+        threads
+    );
+
+    let display = |path: &Path| workspace.display(path, options.full);
+
+    let updates =
+        update_repos(
+            &repos,
+            options.mode,
+            options.timeout,
+            threads,
+            |update| match &update.outcome {
+                UpdateOutcome::Updated => println!("ok       {}", display(&update.path)),
+                UpdateOutcome::NotFastForward => println!("no-ff    {}", display(&update.path)),
+                UpdateOutcome::Skipped => println!("skipped  {}", display(&update.path)),
+                UpdateOutcome::Failed(e) => {
+                    println!("failed   {}", display(&update.path));
+                    log::debug!("{}", e);
+                }
+            },
+        );
+
+    let failed: Vec<&RepoUpdate> = updates
+        .iter()
+        .filter(|u| matches!(u.outcome, UpdateOutcome::Failed(_)))
+        .collect();
+    let not_fast_forward: Vec<&RepoUpdate> = updates
+        .iter()
+        .filter(|u| matches!(u.outcome, UpdateOutcome::NotFastForward))
+        .collect();
+    let skipped = updates
+        .iter()
+        .filter(|u| matches!(u.outcome, UpdateOutcome::Skipped))
+        .count();
+    let succeeded = updates.len() - failed.len() - not_fast_forward.len() - skipped;
+
+    println!();
+    println!(
+        "{} {} repositories: {} succeeded, {} failed, {} could not fast-forward, {} skipped",
+        options.mode.verb(),
+        updates.len(),
+        succeeded,
+        failed.len(),
+        not_fast_forward.len(),
+        skipped
+    );
+    for update in &failed {
+        if let UpdateOutcome::Failed(e) = &update.outcome {
+            println!("  {}: {}", display(&update.path), e);
+        }
+    }
+    for update in &not_fast_forward {
+        println!("  {}: could not fast-forward", display(&update.path));
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(RkitError::GitError(format!(
+            "{} of {} repositories failed to update",
+            failed.len(),
+            updates.len()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit(repo: &Path, file: &str) {
+        fs::write(repo.join(file), file).unwrap();
+        git(repo, &["add", file]);
+        git(repo, &["commit", "-q", "-m", file]);
+    }
+
+    /// Creates a bare remote with one commit, a workspace clone of it, and a
+    /// second clone used to push new commits to the remote
+    fn setup(dir: &Path) -> (PathBuf, PathBuf) {
+        let remote = dir.join("remote.git");
+        let upstream = dir.join("upstream");
+        let local = dir.join("workspace").join("local");
+
+        git(dir, &["init", "-q", "--bare", "-b", "main", "remote.git"]);
+        git(
+            dir,
+            &[
+                "clone",
+                "-q",
+                remote.to_str().unwrap(),
+                upstream.to_str().unwrap(),
+            ],
+        );
+        git(&upstream, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+        commit(&upstream, "one");
+        git(&upstream, &["push", "-q", "origin", "main"]);
+        git(
+            dir,
+            &[
+                "clone",
+                "-q",
+                remote.to_str().unwrap(),
+                local.to_str().unwrap(),
+            ],
+        );
+
+        (upstream, local)
+    }
+
+    #[test]
+    fn test_fetch_and_pull() {
+        let dir = tempdir().unwrap();
+        let (upstream, local) = setup(dir.path());
+        commit(&upstream, "two");
+        git(&upstream, &["push", "-q", "origin", "main"]);
+
+        let update = update_repo(&local, UpdateMode::Fetch, None);
+        assert!(matches!(update.outcome, UpdateOutcome::Updated));
+
+        let update = update_repo(&local, UpdateMode::Pull, None);
+        assert!(matches!(update.outcome, UpdateOutcome::Updated));
+        assert!(local.join("two").exists());
+    }
+
+    #[test]
+    fn test_pull_not_fast_forward() {
+        let dir = tempdir().unwrap();
+        let (upstream, local) = setup(dir.path());
+        commit(&upstream, "two");
+        git(&upstream, &["push", "-q", "origin", "main"]);
+        commit(&local, "diverged");
+
+        let update = update_repo(&local, UpdateMode::Pull, None);
+        assert!(matches!(update.outcome, UpdateOutcome::NotFastForward));
+    }
+
+    #[test]
+    fn test_pull_ahead_with_unreachable_remote_fails() {
+        let dir = tempdir().unwrap();
+        let (_, local) = setup(dir.path());
+        commit(&local, "unpushed");
+        git(
+            &local,
+            &["remote", "set-url", "origin", "/nonexistent/remote.git"],
+        );
+
+        let update = update_repo(&local, UpdateMode::Pull, None);
+        assert!(matches!(
+            update.outcome,
+            UpdateOutcome::Failed(RkitError::GitError(_))
+        ));
+    }
+
+    #[test]
+    fn test_pull_without_upstream_fails() {
+        let dir = tempdir().unwrap();
+        let (_, local) = setup(dir.path());
+        git(&local, &["checkout", "-q", "-b", "topic"]);
+        commit(&local, "topic");
+
+        let update = update_repo(&local, UpdateMode::Pull, None);
+        assert!(matches!(
+            update.outcome,
+            UpdateOutcome::Failed(RkitError::GitError(_))
+        ));
+    }
+
+    #[test]
+    fn test_pull_skips_bare_repos() {
+        let dir = tempdir().unwrap();
+        setup(dir.path());
+        let remote = dir.path().join("remote.git");
+
+        let update = update_repo(&remote, UpdateMode::Pull, None);
+        assert!(matches!(update.outcome, UpdateOutcome::Skipped));
+    }
+
+    #[test]
+    fn test_update_repos_reports_failures_per_repo() {
+        let dir = tempdir().unwrap();
+        let (_, local) = setup(dir.path());
+        let broken = dir.path().join("workspace").join("broken");
+        fs::create_dir_all(&broken).unwrap();
+        git(&broken, &["init", "-q"]);
+        git(
+            &broken,
+            &["remote", "add", "origin", "/nonexistent/remote.git"],
+        );
+
+        let updates = update_repos(&[local, broken], UpdateMode::Fetch, None, 2, |_| {});
+        assert!(matches!(updates[0].outcome, UpdateOutcome::Updated));
+        assert!(matches!(
+            updates[1].outcome,
+            UpdateOutcome::Failed(RkitError::GitError(_))
+        ));
+    }
+}
@@ -1,181 +1,372 @@
-use ignore::{WalkBuilder, WalkState};
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::io;
 use std::io::Write;
-use std::path::Path;
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc, Mutex,
-};
-use std::time::{Duration, Instant};
-
-use crate::cache::{Cache, CacheError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::cache::{Cache, CacheEntry};
 use crate::error::{RkitError, RkitResult};
+use crate::filter::RepoFilter;
+use crate::layout::Layouts;
+use crate::scanner::{RepoKind, RepoScanner};
+use crate::usage::Usage;
+use crate::workspace::{cache_scan, refresh_cache, ProjectRoot, Workspace};
 use crate::CACHE;
 
-#[derive(Debug)]
-struct PerformanceMetrics {
-    total_duration: Duration,
-    repo_count: usize,
-    scanned_dirs: usize,
+pub use crate::scanner::WalkerConfig;
+
+/// Age in seconds after which a listing served from the cached index should
+/// be revalidated with a rescan in the background
+pub const REVALIDATE_AFTER: u64 = 5 * 60;
+
+/// Output format for repository listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One path per line
+    #[default]
+    Plain,
+    /// A single JSON array, printed once the scan completes
+    Json,
+    /// One JSON object per line, streamed as repositories are found
+    Ndjson,
+    /// Paths terminated by NUL bytes, safe for paths containing newlines
+    Null,
+}
+
+/// Order in which `list_repos` prints repositories once the scan completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// By path as printed
+    Path,
+    /// By repository directory name
+    Name,
+    /// Most recently modified first
+    Mtime,
+    /// Most recently visited first, never visited last
+    LastVisited,
+    /// Most frequently and recently visited first, never visited last
+    Frecency,
+}
+
+/// Options controlling how `list_repos` produces its output
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Print absolute paths instead of paths relative to the project root
+    pub full: bool,
+    /// Ignore the cached index and rescan the project root
+    pub refresh: bool,
+    /// Output format
+    pub format: OutputFormat,
+    /// Layouts used to derive domain, organization and repository metadata
+    pub layouts: Layouts,
+    /// Only list repositories matching this filter
+    pub filter: RepoFilter,
+    /// Buffer the listing and print it in this order instead of streaming
+    pub sort: Option<SortKey>,
 }
 
-#[derive(Debug, Clone)]
-pub struct WalkerConfig {
-    pub max_depth: Option<usize>,
-    pub follow_links: bool,
-    pub same_file_system: bool,
-    pub threads: usize,
-    pub max_repos: Option<usize>,
-    pub stop_at_git: bool,
+/// Machine-readable description of a discovered repository
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoRecord {
+    /// Absolute path to the repository
+    pub path: String,
+    /// Name of the project root containing the repository
+    pub root: Option<String>,
+    /// Path relative to the project root
+    pub relative_path: String,
+    /// Domain segment of the clone layout, if the path follows it
+    pub domain: Option<String>,
+    /// Organization segment of the clone layout, if the path follows it
+    pub org: Option<String>,
+    /// Repository segment of the clone layout, if the path follows it
+    pub repo: Option<String>,
+    /// How the repository is laid out on disk, if it still is one
+    pub kind: Option<RepoKind>,
+    /// Last modification time of the repository (seconds since epoch)
+    pub last_modified: u64,
+    /// Last time the cache entry was validated (seconds since epoch)
+    pub last_checked: u64,
 }
 
-impl Default for WalkerConfig {
-    fn default() -> Self {
+impl RepoRecord {
+    pub fn new(entry: &CacheEntry, workspace: &Workspace, layouts: &Layouts) -> Self {
+        let relative_path = workspace.relative(&entry.path);
+        let parsed = layouts.extract(relative_path).unwrap_or_default();
+
         Self {
-            max_depth: Some(4),
-            follow_links: false,
-            same_file_system: true,
-            threads: std::thread::available_parallelism()
-                .map(|n| n.get())
-                .unwrap_or(1),
-            max_repos: None,
-            stop_at_git: true,
+            path: entry.path.to_string_lossy().to_string(),
+            root: workspace.root_of(&entry.path).map(|r| r.name.clone()),
+            relative_path: relative_path.to_string_lossy().to_string(),
+            domain: parsed.domain,
+            org: parsed.org,
+            repo: parsed.repo,
+            kind: RepoKind::detect(&entry.path),
+            last_modified: entry.last_modified,
+            last_checked: entry.last_checked,
         }
     }
 }
 
-pub fn list_repos(project_root: &Path, full: bool, config: Option<WalkerConfig>) -> RkitResult<()> {
-    let config = config.unwrap_or_default();
-    let start = Instant::now();
+/// Prints repositories in the requested format as they are found. Structured
+/// records are only built for formats that need them, and JSON arrays and
+/// sorted listings are buffered until `finish` is called.
+struct RepoPrinter<'a> {
+    workspace: &'a Workspace,
+    full: bool,
+    format: OutputFormat,
+    layouts: &'a Layouts,
+    filter: &'a RepoFilter,
+    sort: Option<SortKey>,
+    sorted: Mutex<Vec<CacheEntry>>,
+    buffered: Mutex<Vec<RepoRecord>>,
+    seen: Mutex<HashSet<PathBuf>>,
+}
 
-    // Validate and update cache before checking
-    if let Err(e) = CACHE.validate_and_update() {
-        match e {
-            CacheError::LockError(msg) => log::warn!("Failed to acquire cache lock: {}", msg),
-            CacheError::DirectoryError(e) => log::warn!("Failed to access cache directory: {}", e),
-            CacheError::IoError(e) => log::warn!("Failed to write cache: {}", e),
-            e => log::warn!("Failed to update cache: {}", e),
+impl<'a> RepoPrinter<'a> {
+    fn new(workspace: &'a Workspace, options: &'a ListOptions) -> Self {
+        Self {
+            workspace,
+            full: options.full,
+            format: options.format,
+            layouts: &options.layouts,
+            filter: &options.filter,
+            sort: options.sort,
+            sorted: Mutex::new(Vec::new()),
+            buffered: Mutex::new(Vec::new()),
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Prints a freshly discovered repository
+    fn print_path(&self, path: &Path) {
+        if !self.selected(path) {
+            return;
+        }
+        match self.format {
+            OutputFormat::Plain | OutputFormat::Null if self.sort.is_none() => {
+                if self.first_sighting(path) {
+                    self.print_plain(path)
+                }
+            }
+            _ => self.emit(&Cache::update_entry(path)),
         }
     }
 
-    // Check cache first without retry
-    if let Some(cached_entry) = CACHE
-        .get(project_root)
-        .filter(|entry| Cache::validate_entry(entry, CACHE.ttl_seconds()))
-    {
-        if full {
-            println!("{}", cached_entry.path.display());
-        } else if let Ok(relative_path) = cached_entry.path.strip_prefix(project_root) {
-            println!("{}", relative_path.display());
+    /// Whether the repository passes the filter
+    fn selected(&self, path: &Path) -> bool {
+        self.filter.is_empty()
+            || self
+                .filter
+                .matches_repo(path, self.workspace.relative(path))
+    }
+
+    /// Whether `path` has not been printed yet, as nested roots can both
+    /// find the same repository
+    fn first_sighting(&self, path: &Path) -> bool {
+        self.seen
+            .lock()
+            .map(|mut seen| seen.insert(path.to_path_buf()))
+            .unwrap_or(true)
+    }
+
+    /// Prints a repository together with its cache metadata
+    fn print_entry(&self, entry: &CacheEntry) {
+        if self.selected(&entry.path) {
+            self.emit(entry);
         }
-        return Ok(());
     }
 
-    // Build parallel walker using configured threads
-    let walker = WalkBuilder::new(project_root)
-        .max_depth(config.max_depth)
-        .follow_links(config.follow_links)
-        .same_file_system(config.same_file_system)
-        .threads(config.threads)
-        .git_ignore(false)
-        .ignore(false)
-        .parents(false)
-        .build_parallel();
-
-    let repo_count = Arc::new(AtomicUsize::new(0));
-    let scanned_dirs = Arc::new(AtomicUsize::new(0));
-    let discovered_repos = Arc::new(Mutex::new(Vec::new()));
-
-    walker.run(|| {
-        let repo_count = Arc::clone(&repo_count);
-        let scanned_dirs = Arc::clone(&scanned_dirs);
-        let discovered_repos = Arc::clone(&discovered_repos);
-        Box::new(move |result| {
-            scanned_dirs.fetch_add(1, Ordering::Relaxed);
-            match result {
-                Ok(entry) => {
-                    // Only check for .git if this entry is a directory
-                    if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-                        // Single stat syscall instead of read_dir + iterate
-                        if entry.path().join(".git").exists() {
-                            repo_count.fetch_add(1, Ordering::Relaxed);
-                            let path = entry.path().to_path_buf();
-
-                            if let Ok(mut repos) = discovered_repos.lock() {
-                                repos.push(path.clone());
-                            }
-
-                            if full {
-                                println!("{}", path.display());
-                            } else if let Ok(relative_path) = path.strip_prefix(project_root) {
-                                println!("{}", relative_path.display());
-                            }
-
-                            if let Some(max_repos) = config.max_repos {
-                                if repo_count.load(Ordering::Relaxed) >= max_repos {
-                                    log::info!(
-                                        "Reached maximum number of repositories ({})",
-                                        max_repos
-                                    );
-                                    return WalkState::Quit;
-                                }
-                            }
-
-                            if config.stop_at_git {
-                                return WalkState::Skip;
-                            }
-                        }
-                    }
-                }
-                Err(e) => log::error!("Error walking directory: {}", e),
+    /// Prints a repository that passed the filter, or holds it back until
+    /// the listing is sorted
+    fn emit(&self, entry: &CacheEntry) {
+        if !self.first_sighting(&entry.path) {
+            return;
+        }
+
+        if self.sort.is_some() {
+            if let Ok(mut sorted) = self.sorted.lock() {
+                sorted.push(entry.clone());
             }
-            WalkState::Continue
-        })
-    });
+        } else {
+            self.output(entry);
+        }
+    }
 
-    let repo_count = repo_count.load(Ordering::Relaxed);
-    let scanned_dirs = scanned_dirs.load(Ordering::Relaxed);
-    let discovered_repos = match Arc::try_unwrap(discovered_repos) {
-        Ok(mutex) => mutex.into_inner().unwrap_or_default(),
-        Err(arc) => arc.lock().map(|g| g.clone()).unwrap_or_default(),
-    };
-
-    // Flush stdout to ensure all output is written
-    io::stdout().flush().map_err(RkitError::IoError)?;
-
-    // Cache all discovered repositories
-    if !discovered_repos.is_empty() {
-        if let Err(e) = CACHE.update_and_save_many(&discovered_repos) {
-            match e {
-                CacheError::LockError(msg) => log::warn!("Failed to acquire cache lock: {}", msg),
-                CacheError::DirectoryError(e) => {
-                    log::warn!("Failed to access cache directory: {}", e)
+    fn output(&self, entry: &CacheEntry) {
+        match self.format {
+            OutputFormat::Plain | OutputFormat::Null => self.print_plain(&entry.path),
+            OutputFormat::Json => {
+                let record = RepoRecord::new(entry, self.workspace, self.layouts);
+                if let Ok(mut buffered) = self.buffered.lock() {
+                    buffered.push(record);
                 }
-                CacheError::IoError(e) => log::warn!("Failed to write cache: {}", e),
-                e => log::warn!("Failed to save discovered repositories to cache: {}", e),
             }
+            OutputFormat::Ndjson => {
+                let record = RepoRecord::new(entry, self.workspace, self.layouts);
+                match serde_json::to_string(&record) {
+                    Ok(line) => println!("{}", line),
+                    Err(e) => log::error!("Failed to serialize {}: {}", entry.path.display(), e),
+                }
+            }
+        }
+    }
+
+    fn print_plain(&self, path: &Path) {
+        let display_path = if self.full {
+            path
+        } else if self.workspace.root_of(path).is_some() {
+            self.workspace.relative(path)
+        } else {
+            return;
+        };
+
+        if self.format == OutputFormat::Null {
+            print!("{}\0", display_path.display());
+        } else {
+            println!("{}", display_path.display());
         }
     }
 
-    let metrics = PerformanceMetrics {
-        total_duration: start.elapsed(),
-        repo_count,
-        scanned_dirs,
-    };
+    /// Writes any buffered output and flushes stdout
+    fn finish(&self) -> RkitResult<()> {
+        if let Some(key) = self.sort {
+            let mut entries = self
+                .sorted
+                .lock()
+                .map(|mut entries| std::mem::take(&mut *entries))
+                .unwrap_or_default();
+            let usage =
+                matches!(key, SortKey::LastVisited | SortKey::Frecency).then(Usage::load_default);
+            sort_entries(&mut entries, key, self.workspace, self.full, usage.as_ref());
+            for entry in &entries {
+                self.output(entry);
+            }
+        }
+
+        if self.format == OutputFormat::Json {
+            let buffered = self
+                .buffered
+                .lock()
+                .map(|records| records.clone())
+                .unwrap_or_default();
+            println!("{}", serde_json::to_string_pretty(&buffered)?);
+        }
+        io::stdout().flush().map_err(RkitError::IoError)
+    }
+}
+
+/// Orders entries by `key`, breaking ties by the printed path so the order is
+/// the same on every run
+fn sort_entries(
+    entries: &mut [CacheEntry],
+    key: SortKey,
+    workspace: &Workspace,
+    full: bool,
+    usage: Option<&Usage>,
+) {
+    let path_key = |entry: &CacheEntry| (workspace.display(&entry.path, full), entry.path.clone());
+    match key {
+        SortKey::Path => entries.sort_by_cached_key(path_key),
+        SortKey::Name => entries.sort_by_cached_key(|entry| {
+            (
+                entry.path.file_name().map(|name| name.to_os_string()),
+                path_key(entry),
+            )
+        }),
+        SortKey::Mtime => {
+            entries.sort_by_cached_key(|entry| (Reverse(entry.last_modified), path_key(entry)))
+        }
+        SortKey::LastVisited => entries.sort_by_cached_key(|entry| {
+            let visited = usage.and_then(|usage| usage.last_visited(&entry.path));
+            (Reverse(visited), path_key(entry))
+        }),
+        SortKey::Frecency => {
+            let score = |entry: &CacheEntry| {
+                usage
+                    .and_then(|usage| usage.frecency(&entry.path))
+                    .unwrap_or_default()
+            };
+            entries.sort_by(|a, b| {
+                score(b)
+                    .total_cmp(&score(a))
+                    .then_with(|| path_key(a).cmp(&path_key(b)))
+            })
+        }
+    }
+}
+
+pub fn list_repos(workspace: &Workspace, options: &ListOptions) -> RkitResult<()> {
+    let printer = RepoPrinter::new(workspace, options);
+    let start = Instant::now();
 
+    // Validate and update cache before checking
+    refresh_cache();
+
+    for root in workspace.roots() {
+        list_root(root, &printer, options.refresh);
+    }
+
+    // Write buffered output and flush stdout to ensure all output is written
+    printer.finish()?;
     log::info!(
-        "Scanned {} directories, found {} repositories in {:?}",
-        metrics.scanned_dirs,
-        metrics.repo_count,
-        metrics.total_duration
+        "Listed repositories in {} roots in {:?}",
+        workspace.roots().len(),
+        start.elapsed()
     );
-
     Ok(())
 }
 
+/// Whether the listing of any root was served from an index older than
+/// [`REVALIDATE_AFTER`]. An expired index is rescanned while listing, so
+/// this only holds for indexes that are still within the cache TTL.
+pub fn needs_revalidation(workspace: &Workspace) -> bool {
+    workspace.roots().iter().any(|root| {
+        CACHE
+            .index_age(&root.path, &root.walker.scan_key())
+            .is_some_and(|age| age > REVALIDATE_AFTER)
+    })
+}
+
+/// Prints the repositories of a single root, serving the listing from the
+/// cached index when it is still fresh
+fn list_root(root: &ProjectRoot, printer: &RepoPrinter, refresh: bool) {
+    let config = root.walker.clone();
+    let scan_key = config.scan_key();
+
+    if !refresh {
+        if let Some(cached_entries) = CACHE.get_index(&root.path, &scan_key) {
+            let limit = config.max_repos.unwrap_or(usize::MAX);
+            for entry in cached_entries.iter().take(limit) {
+                printer.print_entry(entry);
+            }
+            log::info!(
+                "Listed {} cached repositories from {}",
+                cached_entries.len().min(limit),
+                root.name
+            );
+            return;
+        }
+    }
+
+    let discovered_repos = Mutex::new(Vec::new());
+    let metrics = RepoScanner::new(config).scan_with(&root.path, |repo| {
+        printer.print_path(&repo.path);
+        if let Ok(mut repos) = discovered_repos.lock() {
+            repos.push(repo.path);
+        }
+    });
+    let discovered_repos = discovered_repos.into_inner().unwrap_or_default();
+    cache_scan(&root.path, &scan_key, &discovered_repos, &metrics);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layout::Layout;
     use std::fs;
     use std::path::Path;
     use tempfile::tempdir;
@@ -193,7 +384,10 @@ mod tests {
         create_git_repo(&repo2);
 
         // For now, just call the function to check it doesn't error
-        let res = list_repos(dir.path(), false, None);
+        let res = list_repos(
+            &Workspace::single(dir.path(), None),
+            &ListOptions::default(),
+        );
         assert!(res.is_ok());
     }
 
@@ -205,7 +399,11 @@ mod tests {
         create_git_repo(&repo1);
         create_git_repo(&repo2);
 
-        let res = list_repos(dir.path(), true, None);
+        let options = ListOptions {
+            full: true,
+            ..Default::default()
+        };
+        let res = list_repos(&Workspace::single(dir.path(), None), &options);
         assert!(res.is_ok());
     }
 
@@ -222,7 +420,10 @@ mod tests {
             ..Default::default()
         };
 
-        let res = list_repos(dir.path(), false, Some(config));
+        let res = list_repos(
+            &Workspace::single(dir.path(), Some(config)),
+            &ListOptions::default(),
+        );
         assert!(res.is_ok());
     }
 
@@ -239,7 +440,10 @@ mod tests {
             ..Default::default()
         };
 
-        let res = list_repos(dir.path(), false, Some(config));
+        let res = list_repos(
+            &Workspace::single(dir.path(), Some(config)),
+            &ListOptions::default(),
+        );
         assert!(res.is_ok());
     }
 
@@ -254,7 +458,10 @@ mod tests {
             ..Default::default()
         };
 
-        let res = list_repos(dir.path(), false, Some(config));
+        let res = list_repos(
+            &Workspace::single(dir.path(), Some(config)),
+            &ListOptions::default(),
+        );
         assert!(res.is_ok());
     }
 
@@ -265,18 +472,206 @@ mod tests {
         fs::create_dir_all(dir.path().join("not_a_repo")).unwrap();
         fs::create_dir_all(dir.path().join("also_not_a_repo")).unwrap();
 
-        let res = list_repos(dir.path(), false, None);
+        let res = list_repos(
+            &Workspace::single(dir.path(), None),
+            &ListOptions::default(),
+        );
         assert!(res.is_ok());
     }
 
     #[test]
-    fn test_walker_config_default() {
-        let config = WalkerConfig::default();
-        assert_eq!(config.max_depth, Some(4));
-        assert!(!config.follow_links);
-        assert!(config.same_file_system);
-        assert!(config.threads > 0);
-        assert_eq!(config.max_repos, None);
-        assert!(config.stop_at_git);
+    fn test_list_repos_refresh() {
+        let dir = tempdir().unwrap();
+        let repo1 = dir.path().join("repo1");
+        let repo2 = dir.path().join("repo2");
+        create_git_repo(&repo1);
+        let workspace = Workspace::single(dir.path(), None);
+        let scan_key = workspace.default_root().walker.scan_key();
+        let indexed = || {
+            let mut repos: Vec<PathBuf> = CACHE
+                .get_index(dir.path(), &scan_key)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect();
+            repos.sort();
+            repos
+        };
+
+        list_repos(&workspace, &ListOptions::default()).unwrap();
+        assert_eq!(indexed(), vec![repo1.clone()]);
+        assert!(!needs_revalidation(&workspace));
+
+        // A second listing is served from the index, so the new repository
+        // is not scanned yet
+        create_git_repo(&repo2);
+        list_repos(&workspace, &ListOptions::default()).unwrap();
+        assert_eq!(indexed(), vec![repo1.clone()]);
+
+        let options = ListOptions {
+            refresh: true,
+            ..Default::default()
+        };
+        list_repos(&workspace, &options).unwrap();
+        assert_eq!(indexed(), vec![repo1, repo2]);
+    }
+
+    #[test]
+    fn test_list_repos_formats() {
+        let dir = tempdir().unwrap();
+        create_git_repo(&dir.path().join("github.com").join("org").join("repo"));
+
+        for format in [
+            OutputFormat::Plain,
+            OutputFormat::Json,
+            OutputFormat::Ndjson,
+            OutputFormat::Null,
+        ] {
+            let options = ListOptions {
+                format,
+                refresh: true,
+                ..Default::default()
+            };
+            let res = list_repos(&Workspace::single(dir.path(), None), &options);
+            assert!(res.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_sort_entries() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("projects");
+        let entry = |path: &str, last_modified: u64| CacheEntry {
+            path: root.join(path),
+            last_modified,
+            last_checked: 0,
+        };
+        let workspace = Workspace::single(&root, None);
+        let mut entries = vec![
+            entry("github.com/org/zeta", 3),
+            entry("gitlab.com/org/alpha", 1),
+            entry("github.com/other/alpha", 2),
+        ];
+        let order = |entries: &[CacheEntry]| {
+            entries
+                .iter()
+                .map(|e| workspace.display(&e.path, false))
+                .collect::<Vec<_>>()
+        };
+
+        sort_entries(&mut entries, SortKey::Path, &workspace, false, None);
+        assert_eq!(
+            order(&entries),
+            [
+                "github.com/org/zeta",
+                "github.com/other/alpha",
+                "gitlab.com/org/alpha"
+            ]
+        );
+
+        sort_entries(&mut entries, SortKey::Name, &workspace, false, None);
+        assert_eq!(
+            order(&entries),
+            [
+                "github.com/other/alpha",
+                "gitlab.com/org/alpha",
+                "github.com/org/zeta"
+            ]
+        );
+
+        sort_entries(&mut entries, SortKey::Mtime, &workspace, false, None);
+        assert_eq!(
+            order(&entries),
+            [
+                "github.com/org/zeta",
+                "github.com/other/alpha",
+                "gitlab.com/org/alpha"
+            ]
+        );
+
+        let visited = root.join("gitlab.com/org/alpha");
+        fs::create_dir_all(&visited).unwrap();
+        let mut usage = Usage::load(&dir.path().join("usage.json"));
+        usage.record(&visited);
+        sort_entries(
+            &mut entries,
+            SortKey::LastVisited,
+            &workspace,
+            false,
+            Some(&usage),
+        );
+        assert_eq!(
+            order(&entries),
+            [
+                "gitlab.com/org/alpha",
+                "github.com/org/zeta",
+                "github.com/other/alpha"
+            ]
+        );
+
+        let frequent = root.join("github.com/other/alpha");
+        fs::create_dir_all(&frequent).unwrap();
+        usage.record(&frequent);
+        usage.record(&frequent);
+        sort_entries(
+            &mut entries,
+            SortKey::Frecency,
+            &workspace,
+            false,
+            Some(&usage),
+        );
+        assert_eq!(
+            order(&entries),
+            [
+                "github.com/other/alpha",
+                "gitlab.com/org/alpha",
+                "github.com/org/zeta"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repo_record_layout_segments() {
+        let root = Path::new("/projects");
+        let workspace = Workspace::single(root, None);
+        let entry = CacheEntry {
+            path: root
+                .join("gitlab.com")
+                .join("group")
+                .join("sub")
+                .join("repo"),
+            last_modified: 1,
+            last_checked: 2,
+        };
+        let record = RepoRecord::new(&entry, &workspace, &Layouts::default());
+        assert_eq!(record.root.as_deref(), Some("default"));
+        assert_eq!(record.domain.as_deref(), Some("gitlab.com"));
+        assert_eq!(record.org.as_deref(), Some("group/sub"));
+        assert_eq!(record.repo.as_deref(), Some("repo"));
+        assert_eq!(record.last_modified, 1);
+        assert_eq!(record.last_checked, 2);
+
+        let entry = CacheEntry {
+            path: root.join("scratch"),
+            last_modified: 1,
+            last_checked: 2,
+        };
+        let record = RepoRecord::new(&entry, &workspace, &Layouts::default());
+        assert_eq!(record.relative_path, "scratch");
+        assert!(record.domain.is_none());
+
+        let layouts = Layouts::new(Layout::parse("{org}-{repo}").unwrap());
+        let record = RepoRecord::new(&entry, &workspace, &layouts);
+        assert!(record.org.is_none());
+
+        let entry = CacheEntry {
+            path: root.join("imthor-rkit"),
+            last_modified: 1,
+            last_checked: 2,
+        };
+        let record = RepoRecord::new(&entry, &workspace, &layouts);
+        assert_eq!(record.domain, None);
+        assert_eq!(record.org.as_deref(), Some("imthor"));
+        assert_eq!(record.repo.as_deref(), Some("rkit"));
     }
 }
@@ -1,3 +1,9 @@
 pub mod clone;
+pub mod exec;
+pub mod fetch;
 pub mod ls;
+pub mod pick;
+pub mod recent;
+pub mod status;
+pub mod sync;
 pub mod view;
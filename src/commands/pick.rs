@@ -0,0 +1,699 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::commands::view::capture_sections;
+use crate::config::RViewCmd;
+use crate::error::RkitResult;
+use crate::fuzzy::fuzzy_match;
+use crate::layout::Layouts;
+use crate::template::TemplateVars;
+use crate::usage::{record_visit, Usage};
+use crate::workspace::Workspace;
+
+/// Narrowest terminal that still shows the preview pane
+const MIN_PREVIEW_WIDTH: u16 = 80;
+/// How often the picker checks for finished previews while waiting for a key
+const PREVIEW_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options for `pick_repo`
+#[derive(Debug, Clone, Default)]
+pub struct PickOptions {
+    /// Query the picker starts with
+    pub query: String,
+    /// List absolute paths instead of paths relative to their root
+    pub full: bool,
+    /// Show the `rview` sections of the highlighted repository
+    pub preview: bool,
+    /// Ignore the cached index and rescan the project roots
+    pub refresh: bool,
+    /// Layouts used to fill in `{DOMAIN}`, `{ORG}` and `{NAME}` in previews
+    pub layouts: Layouts,
+}
+
+/// An item matching the current query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// Index of the item in the picker's list
+    pub index: usize,
+    /// Character indices of the item that matched the query
+    pub positions: Vec<usize>,
+}
+
+/// Query and selection state of the picker, independent of the terminal
+#[derive(Debug)]
+pub struct Picker {
+    items: Vec<String>,
+    query: String,
+    matches: Vec<Candidate>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(items: Vec<String>) -> Self {
+        let mut picker = Self {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        picker.update();
+        picker
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.update();
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update();
+    }
+
+    /// Deletes the last word of the query, like Ctrl-W in a shell
+    pub fn delete_word(&mut self) {
+        let trimmed = self.query.trim_end();
+        let keep = trimmed
+            .rfind(char::is_whitespace)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.query.truncate(keep);
+        self.update();
+    }
+
+    /// Items matching the query, best match first
+    pub fn matches(&self) -> &[Candidate] {
+        &self.matches
+    }
+
+    /// Row of the highlighted match
+    pub fn selected_row(&self) -> usize {
+        self.selected
+    }
+
+    /// Index of the highlighted item, if anything matches
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).map(|c| c.index)
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Re-ranks the items for the current query. Without a query every item
+    /// is shown in its original order.
+    fn update(&mut self) {
+        let mut scored: Vec<(i64, Candidate)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                fuzzy_match(&self.query, item).map(|found| {
+                    (
+                        found.score,
+                        Candidate {
+                            index,
+                            positions: found.positions,
+                        },
+                    )
+                })
+            })
+            .collect();
+        if !self.query.trim().is_empty() {
+            let items = &self.items;
+            scored.sort_by_key(|(score, candidate)| {
+                (
+                    Reverse(*score),
+                    items[candidate.index].len(),
+                    candidate.index,
+                )
+            });
+        }
+        self.matches = scored.into_iter().map(|(_, candidate)| candidate).collect();
+        self.selected = 0;
+    }
+}
+
+/// What the picker does after a key press
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Accept,
+    Abort,
+}
+
+fn handle_key(picker: &mut Picker, key: KeyEvent, page: usize) -> Action {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let page = page.max(1) as isize;
+    match key.code {
+        KeyCode::Enter => return Action::Accept,
+        KeyCode::Esc => return Action::Abort,
+        KeyCode::Char('c' | 'g' | 'q') if ctrl => return Action::Abort,
+        KeyCode::Up => picker.move_selection(-1),
+        KeyCode::Char('p' | 'k') if ctrl => picker.move_selection(-1),
+        KeyCode::Down => picker.move_selection(1),
+        KeyCode::Char('n' | 'j') if ctrl => picker.move_selection(1),
+        KeyCode::PageUp => picker.move_selection(-page),
+        KeyCode::PageDown => picker.move_selection(page),
+        KeyCode::Backspace => picker.pop(),
+        KeyCode::Char('u') if ctrl => picker.set_query(""),
+        KeyCode::Char('w') if ctrl => picker.delete_word(),
+        KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => picker.push(c),
+        _ => {}
+    }
+    Action::Continue
+}
+
+/// Removes terminal escape sequences and control characters, expanding tabs,
+/// so preview text can be measured and clipped
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI sequences end with a byte in the range @ to ~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC sequences end with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => out.push_str("    "),
+            '\n' => out.push('\n'),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Lines of the preview pane for a repository
+fn render_preview(vars: &TemplateVars, commands: Option<&[RViewCmd]>) -> Vec<String> {
+    match capture_sections(vars, commands) {
+        Ok(sections) => sections
+            .iter()
+            .flat_map(|section| {
+                let mut lines = vec![format!("=== {} ===", section.label)];
+                lines.extend(strip_ansi(&section.output()).lines().map(str::to_string));
+                lines.push(String::new());
+                lines
+            })
+            .collect(),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+/// Renders previews on a background thread, so slow sections never block the
+/// picker. Requests queued while a preview renders are skipped except the
+/// latest, and the thread is left behind when the picker exits.
+struct PreviewWorker {
+    requests: Sender<usize>,
+    results: Receiver<(usize, Vec<String>)>,
+    requested: Option<usize>,
+}
+
+impl PreviewWorker {
+    fn spawn(render: impl Fn(usize) -> Vec<String> + Send + 'static) -> Self {
+        let (requests, pending) = mpsc::channel();
+        let (rendered, results) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(mut index) = pending.recv() {
+                // Skip the items the user has already moved past
+                while let Ok(next) = pending.try_recv() {
+                    index = next;
+                }
+                if rendered.send((index, render(index))).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            requested: None,
+        }
+    }
+
+    /// Asks for the preview of an item, unless it was the last one asked for
+    fn request(&mut self, index: usize) {
+        if self.requested != Some(index) {
+            self.requested = Some(index);
+            let _ = self.requests.send(index);
+        }
+    }
+
+    /// Previews rendered since the last call
+    fn finished(&self) -> impl Iterator<Item = (usize, Vec<String>)> + '_ {
+        self.results.try_iter()
+    }
+}
+
+/// Puts the terminal into raw mode on an alternate screen for as long as it
+/// lives. The picker draws on stderr so stdout only carries the selection.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stderr(), EnterAlternateScreen) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// First character of an item shown in `width` columns, and whether it is
+/// preceded by an ellipsis. Long paths keep their end, where the repository
+/// name is.
+fn clip(chars: &[char], width: usize) -> (usize, bool) {
+    if chars.len() <= width {
+        (0, false)
+    } else {
+        (chars.len() - width.saturating_sub(1), true)
+    }
+}
+
+struct Screen<'a> {
+    picker: &'a Picker,
+    previews: &'a HashMap<usize, Vec<String>>,
+    offset: usize,
+    width: u16,
+    height: u16,
+    list_width: u16,
+}
+
+impl Screen<'_> {
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(out, Hide)?;
+        let rows = self.height.saturating_sub(2) as usize;
+
+        // Prompt and match count
+        // Every line is overwritten and then cleared to its end instead of
+        // clearing the whole screen first, which flickers. The preview pane
+        // is drawn last, over the cleared part of each line.
+        queue!(
+            out,
+            MoveTo(0, 0),
+            SetForegroundColor(Color::Blue),
+            Print("> "),
+            ResetColor,
+            Print(self.picker.query()),
+            Clear(ClearType::UntilNewLine)
+        )?;
+        queue!(
+            out,
+            MoveTo(0, 1),
+            SetForegroundColor(Color::DarkGrey),
+            Print(format!(
+                "  {}/{}",
+                self.picker.matches().len(),
+                self.picker.items().len()
+            )),
+            ResetColor,
+            Clear(ClearType::UntilNewLine)
+        )?;
+
+        let mut visible = self.picker.matches().iter().enumerate().skip(self.offset);
+        for y in 2..rows as u16 + 2 {
+            match visible.next() {
+                Some((row, candidate)) => {
+                    self.draw_item(out, y, candidate, row == self.picker.selected_row())?
+                }
+                None => queue!(out, MoveTo(0, y))?,
+            }
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+
+        if self.list_width < self.width {
+            self.draw_preview(out)?;
+        }
+
+        let cursor = 2 + self.picker.query().chars().count() as u16;
+        queue!(
+            out,
+            MoveTo(cursor.min(self.width.saturating_sub(1)), 0),
+            Show
+        )?;
+        out.flush()
+    }
+
+    fn draw_item(
+        &self,
+        out: &mut impl Write,
+        y: u16,
+        candidate: &Candidate,
+        selected: bool,
+    ) -> io::Result<()> {
+        let chars: Vec<char> = self.picker.items()[candidate.index].chars().collect();
+        let (start, ellipsis) = clip(&chars, self.list_width.saturating_sub(2) as usize);
+
+        queue!(out, MoveTo(0, y))?;
+        if selected {
+            queue!(
+                out,
+                SetForegroundColor(Color::Red),
+                Print("> "),
+                ResetColor,
+                SetAttribute(Attribute::Bold)
+            )?;
+        } else {
+            queue!(out, Print("  "))?;
+        }
+        if ellipsis {
+            queue!(out, Print('…'))?;
+        }
+        for (i, c) in chars.iter().enumerate().skip(start) {
+            if candidate.positions.binary_search(&i).is_ok() {
+                queue!(
+                    out,
+                    SetForegroundColor(Color::Green),
+                    Print(c),
+                    SetForegroundColor(Color::Reset)
+                )?;
+            } else {
+                queue!(out, Print(c))?;
+            }
+        }
+        queue!(out, SetAttribute(Attribute::Reset))
+    }
+
+    fn draw_preview(&self, out: &mut impl Write) -> io::Result<()> {
+        let x = self.list_width;
+        let preview_width = self.width.saturating_sub(x + 2) as usize;
+        let selected = self.picker.selected();
+        let loading = [String::from("Loading…")];
+        let lines = selected.map(|index| {
+            self.previews
+                .get(&index)
+                .map_or(&loading[..], |lines| &lines[..])
+        });
+
+        for y in 0..self.height {
+            queue!(
+                out,
+                MoveTo(x, y),
+                SetForegroundColor(Color::DarkGrey),
+                Print('│'),
+                ResetColor
+            )?;
+            if let Some(line) = lines.and_then(|lines| lines.get(y as usize)) {
+                let clipped: String = line.chars().take(preview_width).collect();
+                queue!(out, MoveTo(x + 2, y), Print(clipped))?;
+            }
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+        Ok(())
+    }
+}
+
+/// Waits for the next terminal event, returning `None` early when a preview
+/// has finished so it can be drawn
+fn next_event(
+    worker: Option<&PreviewWorker>,
+    previews: &mut HashMap<usize, Vec<String>>,
+) -> io::Result<Option<Event>> {
+    let Some(worker) = worker else {
+        return event::read().map(Some);
+    };
+    loop {
+        if event::poll(PREVIEW_POLL_INTERVAL)? {
+            return event::read().map(Some);
+        }
+        let before = previews.len();
+        previews.extend(worker.finished());
+        if previews.len() > before {
+            return Ok(None);
+        }
+    }
+}
+
+/// Runs the interactive picker until an item is accepted or it is cancelled.
+/// `preview` renders the preview pane of an item, on a background thread.
+fn run(
+    picker: &mut Picker,
+    preview: Option<impl Fn(usize) -> Vec<String> + Send + 'static>,
+) -> io::Result<Option<usize>> {
+    let _guard = TerminalGuard::enter()?;
+    let mut worker = preview.map(PreviewWorker::spawn);
+    let mut previews: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut offset = 0;
+
+    loop {
+        // Terminals that do not report a size, such as some ptys, say 0x0
+        let (width, height) = match terminal::size()? {
+            (0, _) | (_, 0) => (80, 24),
+            size => size,
+        };
+        let rows = height.saturating_sub(2).max(1) as usize;
+        let list_width = if worker.is_some() && width >= MIN_PREVIEW_WIDTH {
+            width / 2
+        } else {
+            width
+        };
+
+        // Keep the highlighted row on screen
+        let selected = picker.selected_row();
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + rows {
+            offset = selected + 1 - rows;
+        }
+
+        if let (Some(worker), Some(index)) = (&mut worker, picker.selected()) {
+            if list_width < width && !previews.contains_key(&index) {
+                worker.request(index);
+            }
+        }
+
+        let screen = Screen {
+            picker,
+            previews: &previews,
+            offset,
+            width,
+            height,
+            list_width,
+        };
+        screen.draw(&mut BufWriter::new(io::stderr()))?;
+
+        if let Some(Event::Key(key)) = next_event(worker.as_ref(), &mut previews)? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match handle_key(picker, key, rows) {
+                Action::Continue => {}
+                Action::Accept => return Ok(picker.selected()),
+                Action::Abort => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Lets the user pick a repository with a fuzzy finder, records the visit and
+/// prints its path.
+/// Returns `None` if the picker was cancelled or nothing was found.
+pub fn pick_repo(
+    workspace: &Workspace,
+    commands: Option<&[RViewCmd]>,
+    options: &PickOptions,
+) -> RkitResult<Option<PathBuf>> {
+    let mut repos = workspace.find_repos(options.refresh);
+    if repos.is_empty() {
+        log::warn!("No repositories found");
+        return Ok(None);
+    }
+    // Frequently used repositories come first, so they are picked with an
+    // empty or short query
+    let usage = Usage::load_default();
+    let mut keyed: Vec<(f64, String, PathBuf)> = repos
+        .drain(..)
+        .map(|repo| {
+            let score = usage.frecency(&repo).unwrap_or_default();
+            (score, workspace.display(&repo, options.full), repo)
+        })
+        .collect();
+    keyed.sort_by(|(score_a, a, _), (score_b, b, _)| score_b.total_cmp(score_a).then(a.cmp(b)));
+    let (items, repos): (Vec<String>, Vec<PathBuf>) = keyed
+        .into_iter()
+        .map(|(_, display, repo)| (display, repo))
+        .unzip();
+
+    let mut picker = Picker::new(items);
+    picker.set_query(&options.query);
+
+    let render = {
+        let workspace = workspace.clone();
+        let layouts = options.layouts.clone();
+        let repos = repos.clone();
+        let commands = commands.map(<[RViewCmd]>::to_vec);
+        move |index: usize| {
+            let vars = TemplateVars::for_repo(&workspace, &layouts, &repos[index]);
+            render_preview(&vars, commands.as_deref())
+        }
+    };
+    let picked = run(&mut picker, options.preview.then_some(render))?.map(|i| repos[i].clone());
+    if let Some(path) = &picked {
+        record_visit(&Usage::default_path(), path);
+        println!("{}", path.display());
+    }
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> Picker {
+        Picker::new(vec![
+            "github.com/imthor/rkit".to_string(),
+            "github.com/rust-lang/cargo".to_string(),
+            "gitlab.com/team/api".to_string(),
+        ])
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_picker_query() {
+        let mut picker = picker();
+        assert_eq!(picker.matches().len(), 3);
+        assert_eq!(picker.selected(), Some(0));
+
+        picker.set_query("cargo");
+        assert_eq!(picker.selected(), Some(1));
+        picker.set_query("zzz");
+        assert_eq!(picker.selected(), None);
+
+        picker.set_query("lab api");
+        picker.delete_word();
+        assert_eq!(picker.query(), "lab ");
+        assert_eq!(picker.selected(), Some(2));
+    }
+
+    #[test]
+    fn test_picker_keys() {
+        let mut picker = picker();
+        let none = KeyModifiers::NONE;
+        let ctrl = KeyModifiers::CONTROL;
+
+        assert_eq!(
+            handle_key(&mut picker, key(KeyCode::Down, none), 10),
+            Action::Continue
+        );
+        assert_eq!(picker.selected(), Some(1));
+        handle_key(&mut picker, key(KeyCode::PageDown, none), 10);
+        assert_eq!(picker.selected(), Some(2));
+        handle_key(&mut picker, key(KeyCode::Char('p'), ctrl), 10);
+        assert_eq!(picker.selected(), Some(1));
+
+        for c in "rkit".chars() {
+            handle_key(&mut picker, key(KeyCode::Char(c), none), 10);
+        }
+        assert_eq!(picker.query(), "rkit");
+        assert_eq!(picker.selected(), Some(0));
+        handle_key(&mut picker, key(KeyCode::Backspace, none), 10);
+        assert_eq!(picker.query(), "rki");
+        handle_key(&mut picker, key(KeyCode::Char('u'), ctrl), 10);
+        assert_eq!(picker.query(), "");
+
+        assert_eq!(
+            handle_key(&mut picker, key(KeyCode::Enter, none), 10),
+            Action::Accept
+        );
+        assert_eq!(
+            handle_key(&mut picker, key(KeyCode::Char('c'), ctrl), 10),
+            Action::Abort
+        );
+    }
+
+    #[test]
+    fn test_preview_worker() {
+        let mut worker = PreviewWorker::spawn(|index| {
+            thread::sleep(Duration::from_millis(50));
+            vec![index.to_string()]
+        });
+        worker.request(1);
+        worker.request(1);
+        worker.request(2);
+
+        let mut previews = HashMap::new();
+        for _ in 0..100 {
+            previews.extend(worker.finished());
+            if previews.contains_key(&2) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(previews.get(&2), Some(&vec!["2".to_string()]));
+        // A repeated request for the same item is only rendered once
+        assert!(previews.len() <= 2);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[31mmain\x1b[m\tok\r\n\x1b]8;;url\x07link"),
+            "main    ok\nlink"
+        );
+    }
+
+    #[test]
+    fn test_clip() {
+        let chars: Vec<char> = "github.com/org/repo".chars().collect();
+        assert_eq!(clip(&chars, 40), (0, false));
+        let (start, ellipsis) = clip(&chars, 8);
+        assert!(ellipsis);
+        let visible: String = chars[start..].iter().collect();
+        assert_eq!(visible, "org/repo"[1..]);
+    }
+}
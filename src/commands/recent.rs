@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use crate::error::RkitResult;
+use crate::scanner::RepoKind;
+use crate::usage::Usage;
+use crate::workspace::Workspace;
+
+/// Options controlling how `recent_repos` lists repositories
+#[derive(Debug, Clone, Default)]
+pub struct RecentOptions {
+    /// Print at most this many repositories
+    pub limit: Option<usize>,
+    /// Print absolute paths instead of paths relative to their root
+    pub full: bool,
+    /// Print the frecency score before each path
+    pub scores: bool,
+}
+
+/// Visited repositories of the workspace with their frecency, highest first.
+/// Repositories outside the workspace or no longer on disk are skipped.
+pub fn ranked_repos<'a>(workspace: &Workspace, usage: &'a Usage) -> Vec<(&'a Path, f64)> {
+    usage
+        .ranked()
+        .into_iter()
+        .filter(|(repo, _)| workspace.root_of(repo).is_some() && RepoKind::detect(repo).is_some())
+        .collect()
+}
+
+pub fn recent_repos(workspace: &Workspace, options: &RecentOptions) -> RkitResult<()> {
+    let usage = Usage::load_default();
+    let ranked = ranked_repos(workspace, &usage);
+    if ranked.is_empty() {
+        log::info!("No visited repositories yet");
+    }
+
+    for (repo, score) in ranked.into_iter().take(options.limit.unwrap_or(usize::MAX)) {
+        let display = workspace.display(repo, options.full);
+        if options.scores {
+            println!("{:>8.1}  {}", score, display);
+        } else {
+            println!("{}", display);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ranked_repos() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("projects");
+        let daily = root.join("github.com/org/daily");
+        let once = root.join("github.com/org/once");
+        let removed = root.join("github.com/org/removed");
+        let outside = dir.path().join("elsewhere/repo");
+        for repo in [&daily, &once, &outside] {
+            fs::create_dir_all(repo.join(".git")).unwrap();
+        }
+
+        let mut usage = Usage::load(&dir.path().join("usage.json"));
+        for repo in [&daily, &daily, &once, &removed, &outside] {
+            usage.record(repo);
+        }
+
+        let workspace = Workspace::single(&root, None);
+        let ranked: Vec<&Path> = ranked_repos(&workspace, &usage)
+            .into_iter()
+            .map(|(repo, _)| repo)
+            .collect();
+        assert_eq!(ranked, vec![daily.as_path(), once.as_path()]);
+    }
+}
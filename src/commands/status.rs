@@ -0,0 +1,362 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{RkitError, RkitResult};
+use crate::scanner::RepoKind;
+use crate::workers::run_parallel;
+use crate::workspace::Workspace;
+
+/// Output format for `rkit status`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StatusFormat {
+    /// Compact aligned table
+    #[default]
+    Table,
+    /// A single JSON array
+    Json,
+}
+
+/// Options controlling which repositories `status_repos` reports and how
+#[derive(Debug, Clone, Default)]
+pub struct StatusOptions {
+    /// Print absolute paths instead of paths relative to the project root
+    pub full: bool,
+    /// Ignore the cached index and rescan the project root
+    pub refresh: bool,
+    /// Output format
+    pub format: StatusFormat,
+    /// Only report repositories with uncommitted changes or untracked files
+    pub dirty: bool,
+    /// Only report repositories with commits not pushed to their upstream
+    pub ahead: bool,
+    /// Only report repositories with upstream commits not yet pulled
+    pub behind: bool,
+}
+
+/// Working tree state of a single repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepoStatus {
+    /// Absolute path to the repository
+    pub path: String,
+    /// Path relative to the project root
+    pub relative_path: String,
+    /// Checked out branch, or `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// Upstream branch the current branch tracks, if any
+    pub upstream: Option<String>,
+    /// Commits on the current branch that are not on its upstream
+    pub ahead: u64,
+    /// Commits on the upstream that are not on the current branch
+    pub behind: u64,
+    /// Number of tracked files with staged or unstaged changes
+    pub changed: usize,
+    /// Number of untracked files
+    pub untracked: usize,
+    /// Number of stash entries
+    pub stashes: usize,
+    /// Whether the working tree has changes or untracked files
+    pub dirty: bool,
+    /// Whether this is a bare repository, which has no working tree to report
+    pub bare: bool,
+    /// Error reported by git if the status could not be read
+    pub error: Option<String>,
+}
+
+impl RepoStatus {
+    fn matches(&self, options: &StatusOptions) -> bool {
+        (!options.dirty || self.dirty)
+            && (!options.ahead || self.ahead > 0)
+            && (!options.behind || self.behind > 0)
+    }
+}
+
+/// Parses the output of `git status --porcelain=v2 --branch --show-stash`
+fn parse_porcelain_v2(output: &str, status: &mut RepoStatus) {
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.head" if value != "(detached)" => status.branch = Some(value.to_string()),
+                "branch.upstream" => status.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for part in value.split_whitespace() {
+                        if let Some(ahead) = part.strip_prefix('+') {
+                            status.ahead = ahead.parse().unwrap_or(0);
+                        } else if let Some(behind) = part.strip_prefix('-') {
+                            status.behind = behind.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                "stash" => status.stashes = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        } else if line.starts_with("1 ") || line.starts_with("2 ") || line.starts_with("u ") {
+            status.changed += 1;
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        }
+    }
+    status.dirty = status.changed > 0 || status.untracked > 0;
+}
+
+/// Reads the working tree state of a repository with a single git invocation.
+/// Bare repositories are reported as such without running git.
+pub fn repo_status(repo_path: &Path, project_root: &Path) -> RkitResult<RepoStatus> {
+    let mut status = RepoStatus {
+        path: repo_path.to_string_lossy().to_string(),
+        relative_path: repo_path
+            .strip_prefix(project_root)
+            .unwrap_or(repo_path)
+            .to_string_lossy()
+            .to_string(),
+        ..Default::default()
+    };
+    if RepoKind::detect(repo_path) == Some(RepoKind::Bare) {
+        status.bare = true;
+        return Ok(status);
+    }
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["status", "--porcelain=v2", "--branch", "--show-stash"])
+        .output()
+        .map_err(|e| RkitError::ShellCommandError {
+            command: format!("git -C {} status", repo_path.display()),
+            source: e,
+        })?;
+
+    if !output.status.success() {
+        return Err(RkitError::GitError(format!(
+            "git status failed in {}: {}",
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_porcelain_v2(&String::from_utf8_lossy(&output.stdout), &mut status);
+    Ok(status)
+}
+
+/// Collects the status of the given repositories in parallel, recording git
+/// failures on the affected repository instead of aborting
+pub fn collect_statuses(
+    repos: &[PathBuf],
+    workspace: &Workspace,
+    threads: usize,
+) -> Vec<RepoStatus> {
+    let mut statuses = run_parallel(repos, threads, |repo| {
+        let project_root = workspace
+            .root_of(repo)
+            .map_or(Path::new(""), |root| root.path.as_path());
+        repo_status(repo, project_root).unwrap_or_else(|e| {
+            log::warn!("{}", e);
+            RepoStatus {
+                path: repo.to_string_lossy().to_string(),
+                relative_path: workspace.relative(repo).to_string_lossy().to_string(),
+                error: Some(e.to_string()),
+                ..Default::default()
+            }
+        })
+    });
+    statuses.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    statuses
+}
+
+fn format_state(status: &RepoStatus) -> String {
+    if status.error.is_some() {
+        "error".to_string()
+    } else if status.bare {
+        "bare".to_string()
+    } else if status.dirty {
+        "dirty".to_string()
+    } else {
+        "clean".to_string()
+    }
+}
+
+fn print_table(statuses: &[RepoStatus], full: bool) {
+    let headers = [
+        "REPO",
+        "BRANCH",
+        "STATE",
+        "CHANGED",
+        "UNTRACKED",
+        "STASH",
+        "AHEAD",
+        "BEHIND",
+    ];
+    let rows: Vec<[String; 8]> = statuses
+        .iter()
+        .map(|status| {
+            [
+                if full {
+                    status.path.clone()
+                } else {
+                    status.relative_path.clone()
+                },
+                match &status.branch {
+                    Some(branch) => branch.clone(),
+                    None if status.error.is_some() || status.bare => "-".to_string(),
+                    None => "(detached)".to_string(),
+                },
+                format_state(status),
+                status.changed.to_string(),
+                status.untracked.to_string(),
+                status.stashes.to_string(),
+                status.ahead.to_string(),
+                status.behind.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = headers.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+pub fn status_repos(workspace: &Workspace, options: &StatusOptions) -> RkitResult<()> {
+    let repos = workspace.find_repos(options.refresh);
+    log::info!("Collecting status for {} repositories", repos.len());
+
+    let statuses: Vec<RepoStatus> = collect_statuses(&repos, workspace, workspace.threads())
+        .into_iter()
+        .filter(|status| status.matches(options))
+        .collect();
+
+    match options.format {
+        StatusFormat::Table => print_table(&statuses, options.full),
+        StatusFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_porcelain_v2() {
+        let output = "\
+# branch.oid 0123456789abcdef
+# branch.head main
+# branch.upstream origin/main
+# branch.ab +2 -3
+# stash 1
+1 .M N... 100644 100644 100644 abc abc src/lib.rs
+2 R. N... 100644 100644 100644 abc abc R100 new.rs\told.rs
+? notes.txt
+? scratch/
+";
+        let mut status = RepoStatus::default();
+        parse_porcelain_v2(output, &mut status);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!(status.ahead, 2);
+        assert_eq!(status.behind, 3);
+        assert_eq!(status.stashes, 1);
+        assert_eq!(status.changed, 2);
+        assert_eq!(status.untracked, 2);
+        assert!(status.dirty);
+    }
+
+    #[test]
+    fn test_parse_porcelain_v2_detached_clean() {
+        let output = "# branch.oid 0123456789abcdef\n# branch.head (detached)\n";
+        let mut status = RepoStatus::default();
+        parse_porcelain_v2(output, &mut status);
+        assert_eq!(status.branch, None);
+        assert!(!status.dirty);
+    }
+
+    #[test]
+    fn test_status_filters() {
+        let status = RepoStatus {
+            dirty: true,
+            ahead: 1,
+            ..Default::default()
+        };
+        let dirty_only = StatusOptions {
+            dirty: true,
+            ..Default::default()
+        };
+        let behind_only = StatusOptions {
+            behind: true,
+            ..Default::default()
+        };
+        assert!(status.matches(&StatusOptions::default()));
+        assert!(status.matches(&dirty_only));
+        assert!(!status.matches(&behind_only));
+    }
+
+    #[test]
+    fn test_repo_status() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("file.txt"), "one").unwrap();
+        git(&repo, &["add", "file.txt"]);
+        git(&repo, &["commit", "-q", "-m", "initial"]);
+        fs::write(repo.join("file.txt"), "two").unwrap();
+        fs::write(repo.join("new.txt"), "new").unwrap();
+
+        let status = repo_status(&repo, dir.path()).unwrap();
+        assert_eq!(status.relative_path, "repo");
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.changed, 1);
+        assert_eq!(status.untracked, 1);
+        assert!(status.dirty);
+    }
+
+    #[test]
+    fn test_repo_status_bare() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo.git");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q", "--bare"]);
+
+        let status = repo_status(&repo, dir.path()).unwrap();
+        assert!(status.bare);
+        assert_eq!(status.error, None);
+        assert_eq!(format_state(&status), "bare");
+        assert!(!status.matches(&StatusOptions {
+            dirty: true,
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_collect_statuses_records_errors() {
+        let dir = tempdir().unwrap();
+        let not_git = dir.path().join("not_git");
+        fs::create_dir_all(&not_git).unwrap();
+
+        let statuses = collect_statuses(&[not_git], &Workspace::single(dir.path(), None), 2);
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].error.is_some());
+    }
+}
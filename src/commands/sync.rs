@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::commands::clone::{clone_into, CloneOptions};
+use crate::commands::fetch::{update_repo, UpdateMode, UpdateOutcome};
+use crate::config::CloneFlags;
+use crate::error::{RkitError, RkitResult};
+use crate::git::origin_url;
+use crate::layout::Layout;
+use crate::manifest::{Manifest, ManifestRepo};
+use crate::repo_url::parse_repo_url;
+use crate::scanner::RepoKind;
+use crate::workers::run_parallel;
+use crate::workspace::Workspace;
+
+/// Options for `sync_workspace`
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Layouts and clone flags used for missing repositories
+    pub clone: CloneOptions,
+    /// Fetch repositories that are already present
+    pub fetch: bool,
+    /// Maximum time a single fetch may take before it is killed
+    pub timeout: Option<Duration>,
+    /// Only sync manifest entries with at least one of these tags
+    pub tags: Vec<String>,
+    /// Print absolute paths instead of paths relative to the project root
+    pub full: bool,
+    /// Ignore the cached index and rescan the project root
+    pub refresh: bool,
+}
+
+/// Where and how a manifest entry is cloned
+#[derive(Debug, Clone)]
+pub struct PlannedRepo {
+    pub url: String,
+    pub target: PathBuf,
+    pub flags: CloneFlags,
+}
+
+/// What happened to a single manifest entry
+#[derive(Debug)]
+pub enum SyncOutcome {
+    Cloned,
+    Fetched,
+    /// Already present and not fetched
+    Present,
+    Failed(RkitError),
+}
+
+/// Result of syncing a single manifest entry
+#[derive(Debug)]
+pub struct RepoSync {
+    pub path: PathBuf,
+    pub url: String,
+    pub outcome: SyncOutcome,
+}
+
+/// Resolves where and how a manifest entry is cloned
+pub fn plan_repo(
+    repo: &ManifestRepo,
+    workspace: &Workspace,
+    options: &CloneOptions,
+) -> RkitResult<PlannedRepo> {
+    let parsed = parse_repo_url(&repo.url)?;
+    let root = match &repo.root {
+        Some(name) => workspace.root(name)?,
+        None => options.root_for(workspace, &parsed.domain)?,
+    };
+    let relative_path = match &repo.layout {
+        Some(template) => Layout::parse(template)?.render(&parsed),
+        None => options.layouts.render(&parsed),
+    };
+
+    let mut flags = options.flags_for(&parsed.domain);
+    if repo.branch.is_some() {
+        flags.branch = repo.branch.clone();
+    }
+
+    Ok(PlannedRepo {
+        url: repo.url.clone(),
+        target: root.path.join(relative_path),
+        flags,
+    })
+}
+
+/// Resolves the clone target of every manifest entry, failing if two entries
+/// would be cloned to the same directory
+pub fn plan_manifest(
+    manifest: &Manifest,
+    workspace: &Workspace,
+    options: &CloneOptions,
+) -> RkitResult<Vec<PlannedRepo>> {
+    let mut planned: Vec<PlannedRepo> = Vec::with_capacity(manifest.repos.len());
+    for repo in &manifest.repos {
+        let next = plan_repo(repo, workspace, options)?;
+        if let Some(existing) = planned.iter().find(|p| p.target == next.target) {
+            return Err(RkitError::ConfigError(format!(
+                "Manifest entries {} and {} would both be cloned to {}",
+                existing.url,
+                next.url,
+                next.target.display()
+            )));
+        }
+        planned.push(next);
+    }
+    Ok(planned)
+}
+
+/// Clones a planned repository if it is missing, or fetches it if requested.
+/// A target that exists but is not a repository is reported as a failure.
+pub fn sync_repo(planned: &PlannedRepo, fetch: bool, timeout: Option<Duration>) -> RepoSync {
+    let outcome = if !planned.target.exists() {
+        match clone_into(&planned.url, &planned.target, &planned.flags, true) {
+            Ok(()) => SyncOutcome::Cloned,
+            Err(e) => SyncOutcome::Failed(e),
+        }
+    } else if RepoKind::detect(&planned.target).is_none() {
+        SyncOutcome::Failed(RkitError::TargetExistsError {
+            path: planned.target.clone(),
+            reason: "not a git repository".to_string(),
+        })
+    } else if fetch {
+        match update_repo(&planned.target, UpdateMode::Fetch, timeout).outcome {
+            UpdateOutcome::Updated => SyncOutcome::Fetched,
+            UpdateOutcome::NotFastForward => SyncOutcome::Failed(RkitError::GitError(format!(
+                "{} could not be fast-forwarded",
+                planned.target.display()
+            ))),
+            UpdateOutcome::Skipped => SyncOutcome::Present,
+            UpdateOutcome::Failed(e) => SyncOutcome::Failed(e),
+        }
+    } else {
+        SyncOutcome::Present
+    };
+
+    RepoSync {
+        path: planned.target.clone(),
+        url: planned.url.clone(),
+        outcome,
+    }
+}
+
+/// Repositories under the project root whose path is not planned by the
+/// manifest
+pub fn find_extras(repos: &[PathBuf], planned: &[PlannedRepo]) -> Vec<PathBuf> {
+    let targets: HashSet<&PathBuf> = planned.iter().map(|p| &p.target).collect();
+    repos
+        .iter()
+        .filter(|repo| !targets.contains(repo))
+        .cloned()
+        .collect()
+}
+
+pub fn sync_workspace(
+    workspace: &Workspace,
+    manifest: &Manifest,
+    options: &SyncOptions,
+) -> RkitResult<()> {
+    let threads = workspace.threads();
+    let planned = plan_manifest(manifest, workspace, &options.clone)?;
+    let selected: Vec<PlannedRepo> = planned
+        .iter()
+        .zip(&manifest.repos)
+        .filter(|(_, repo)| repo.has_any_tag(&options.tags))
+        .map(|(planned, _)| planned.clone())
+        .collect();
+    log::info!(
+        "Syncing {} of {} manifest entries with {} workers",
+        selected.len(),
+        planned.len(),
+        threads
+    );
+
+    let display = |path: &Path| workspace.display(path, options.full);
+
+    let results = run_parallel(&selected, threads, |repo| {
+        let result = sync_repo(repo, options.fetch, options.timeout);
+        match &result.outcome {
+            SyncOutcome::Cloned => println!("cloned   {}", display(&result.path)),
+            SyncOutcome::Fetched => println!("fetched  {}", display(&result.path)),
+            SyncOutcome::Present => println!("present  {}", display(&result.path)),
+            SyncOutcome::Failed(e) => {
+                println!("failed   {}", display(&result.path));
+                log::debug!("{}", e);
+            }
+        }
+        result
+    });
+
+    // Extras are judged against the whole manifest, not only selected tags
+    let repos = workspace.find_repos(options.refresh);
+    let extras = find_extras(&repos, &planned);
+    for extra in &extras {
+        println!("extra    {}", display(extra));
+    }
+
+    let count = |f: fn(&SyncOutcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    let cloned = count(|o| matches!(o, SyncOutcome::Cloned));
+    let fetched = count(|o| matches!(o, SyncOutcome::Fetched));
+    let present = count(|o| matches!(o, SyncOutcome::Present));
+    let failed: Vec<&RepoSync> = results
+        .iter()
+        .filter(|r| matches!(r.outcome, SyncOutcome::Failed(_)))
+        .collect();
+
+    println!();
+    println!(
+        "Synced {} repositories: {} cloned, {} fetched, {} already present, {} failed; {} not in manifest",
+        results.len(),
+        cloned,
+        fetched,
+        present,
+        failed.len(),
+        extras.len()
+    );
+    for result in &failed {
+        if let SyncOutcome::Failed(e) = &result.outcome {
+            println!("  {} ({}): {}", display(&result.path), result.url, e);
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(RkitError::GitError(format!(
+            "{} of {} repositories failed to sync",
+            failed.len(),
+            results.len()
+        )))
+    }
+}
+
+/// Manifest entry for a repository, adding root and layout overrides when
+/// the repository is not where the configured rules would clone it
+fn manifest_entry(
+    repo: &Path,
+    workspace: &Workspace,
+    options: &CloneOptions,
+) -> Option<ManifestRepo> {
+    let Some(url) = origin_url(repo) else {
+        log::warn!("Skipping {}: no origin remote", repo.display());
+        return None;
+    };
+
+    let mut entry = ManifestRepo {
+        url,
+        branch: None,
+        layout: None,
+        root: None,
+        tags: Vec::new(),
+    };
+
+    let relative_path = workspace.relative(repo);
+    let Ok(parsed) = parse_repo_url(&entry.url) else {
+        log::warn!(
+            "Origin of {} is not a recognized URL: {}",
+            repo.display(),
+            entry.url
+        );
+        return Some(entry);
+    };
+    let actual_root = workspace.root_of(repo).map(|r| r.name.as_str());
+    let rule_root = options
+        .root_for(workspace, &parsed.domain)
+        .ok()
+        .map(|r| r.name.as_str());
+    if actual_root != rule_root {
+        entry.root = actual_root.map(str::to_string);
+    }
+    if options.layouts.render(&parsed) == relative_path {
+        return Some(entry);
+    }
+
+    let parts: Vec<String> = relative_path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    match parts.split_last() {
+        Some((name, parents)) if *name == parsed.repo => {
+            let mut layout = parents.join("/");
+            if !layout.is_empty() {
+                layout.push('/');
+            }
+            layout.push_str("{repo}");
+            entry.layout = Some(layout);
+        }
+        _ => log::warn!(
+            "{} does not follow the layout and cannot be described by a template",
+            repo.display()
+        ),
+    }
+    Some(entry)
+}
+
+/// Builds a manifest describing every repository in the workspace
+pub fn export_manifest(workspace: &Workspace, options: &CloneOptions, refresh: bool) -> Manifest {
+    let mut repos = workspace.find_repos(refresh);
+    repos.sort();
+    Manifest {
+        repos: repos
+            .iter()
+            .filter_map(|repo| manifest_entry(repo, workspace, options))
+            .collect(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{bare_remote, git};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn manifest(urls: &[String]) -> Manifest {
+        Manifest {
+            repos: urls
+                .iter()
+                .map(|url| ManifestRepo {
+                    url: url.clone(),
+                    branch: None,
+                    layout: None,
+                    root: None,
+                    tags: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_sync_clones_missing_and_fetches_present() {
+        let dir = tempdir().unwrap();
+        let remote = bare_remote(dir.path());
+
+        let root = dir.path().join("projects");
+        let url = format!("file://{}", remote.display());
+        let planned = plan_manifest(
+            &manifest(&[url]),
+            &Workspace::single(&root, None),
+            &CloneOptions::default(),
+        )
+        .unwrap();
+
+        let result = sync_repo(&planned[0], true, None);
+        assert!(matches!(result.outcome, SyncOutcome::Cloned));
+        assert!(root.join("local/org/repo/.git").exists());
+
+        let result = sync_repo(&planned[0], true, None);
+        assert!(matches!(result.outcome, SyncOutcome::Fetched));
+        let result = sync_repo(&planned[0], false, None);
+        assert!(matches!(result.outcome, SyncOutcome::Present));
+    }
+
+    #[test]
+    fn test_sync_fails_on_non_repo_target() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let planned = plan_manifest(
+            &manifest(&["https://github.com/org/a".to_string()]),
+            &Workspace::single(root, None),
+            &CloneOptions::default(),
+        )
+        .unwrap();
+        fs::create_dir_all(&planned[0].target).unwrap();
+        fs::write(planned[0].target.join("notes.txt"), "notes").unwrap();
+
+        for fetch in [true, false] {
+            let result = sync_repo(&planned[0], fetch, None);
+            assert!(matches!(
+                result.outcome,
+                SyncOutcome::Failed(RkitError::TargetExistsError { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_plan_manifest() {
+        let root = Path::new("/projects");
+        let manifest: Manifest = serde_yml::from_str(
+            r#"
+repos:
+  - url: https://github.com/imthor/rkit
+    branch: dev
+  - url: git@gitlab.com:group/sub/project.git
+    layout: "work/{repo}"
+"#,
+        )
+        .unwrap();
+
+        let planned = plan_manifest(
+            &manifest,
+            &Workspace::single(root, None),
+            &CloneOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(planned[0].target, root.join("github.com/imthor/rkit"));
+        assert_eq!(planned[0].flags.branch.as_deref(), Some("dev"));
+        assert_eq!(planned[1].target, root.join("work/project"));
+    }
+
+    #[test]
+    fn test_plan_manifest_duplicate_targets() {
+        let manifest: Manifest = serde_yml::from_str(
+            r#"
+repos:
+  - url: https://github.com/imthor/rkit
+  - url: git@github.com:imthor/rkit.git
+"#,
+        )
+        .unwrap();
+
+        let result = plan_manifest(
+            &manifest,
+            &Workspace::single(Path::new("/projects"), None),
+            &CloneOptions::default(),
+        );
+        assert!(matches!(result, Err(RkitError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_find_extras() {
+        let root = Path::new("/projects");
+        let planned = plan_manifest(
+            &manifest(&["https://github.com/org/a".to_string()]),
+            &Workspace::single(root, None),
+            &CloneOptions::default(),
+        )
+        .unwrap();
+        let repos = vec![root.join("github.com/org/a"), root.join("scratch")];
+        assert_eq!(find_extras(&repos, &planned), vec![root.join("scratch")]);
+    }
+
+    #[test]
+    fn test_export_manifest() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let standard = root.join("github.com").join("org").join("a");
+        let moved = root.join("misc").join("b");
+        let no_origin = root.join("scratch");
+        for repo in [&standard, &moved, &no_origin] {
+            fs::create_dir_all(repo).unwrap();
+            git(repo, &["init", "-q"]);
+        }
+        git(
+            &standard,
+            &["remote", "add", "origin", "https://github.com/org/a.git"],
+        );
+        git(
+            &moved,
+            &["remote", "add", "origin", "git@github.com:org/b.git"],
+        );
+
+        let workspace = Workspace::single(root, None);
+        let manifest = export_manifest(&workspace, &CloneOptions::default(), true);
+        assert_eq!(manifest.repos.len(), 2);
+        assert_eq!(manifest.repos[0].url, "https://github.com/org/a.git");
+        assert_eq!(manifest.repos[0].layout, None);
+        assert_eq!(manifest.repos[1].url, "git@github.com:org/b.git");
+        assert_eq!(manifest.repos[1].layout.as_deref(), Some("misc/{repo}"));
+
+        // The exported manifest reproduces the existing paths
+        let planned = plan_manifest(&manifest, &workspace, &CloneOptions::default()).unwrap();
+        assert_eq!(planned[0].target, standard);
+        assert_eq!(planned[1].target, moved);
+    }
+}
@@ -1,10 +1,398 @@
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
-use crate::config::RViewCmd;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::config::{RViewCmd, SectionKind};
 use crate::error::{RkitError, RkitResult};
+use crate::process::output_with_timeout;
+use crate::scanner::RepoKind;
+use crate::sections;
+use crate::template::TemplateVars;
+use crate::usage::{record_visit, Usage};
+
+/// Output of a single `rview` section, captured instead of printed
+#[derive(Debug, Clone, Default)]
+pub struct SectionOutput {
+    pub label: String,
+    pub stdout: String,
+    /// Also holds the reason when the command could not be run
+    pub stderr: String,
+    /// Exit code, or `None` if the command did not run to completion
+    pub status: Option<i32>,
+    /// Whether the command was killed for exceeding the section's timeout
+    pub timed_out: bool,
+    pub duration: Duration,
+}
+
+impl SectionOutput {
+    fn text(label: &str, stdout: String) -> Self {
+        Self {
+            label: label.to_string(),
+            stdout,
+            status: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// stdout followed by stderr, ending with a placeholder if the section
+    /// timed out
+    pub fn output(&self) -> String {
+        let mut output = format!("{}{}", self.stdout, self.stderr);
+        if self.timed_out {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&self.timed_out_message());
+        }
+        output
+    }
+
+    fn timed_out_message(&self) -> String {
+        format!("[timed out after {:.1}s]", self.duration.as_secs_f64())
+    }
+}
+
+/// A section as printed by `rkit view --json`
+#[derive(Debug, serde::Serialize)]
+struct SectionRecord<'a> {
+    stdout: &'a str,
+    stderr: &'a str,
+    /// Exit code, `null` if the command could not be run, timed out or was
+    /// killed by a signal
+    status: Option<i32>,
+    timed_out: bool,
+    duration_ms: u64,
+}
+
+/// Sections serialized as one object keyed by label, in configured order.
+/// Repeated labels get a ` (2)`, ` (3)`... suffix so no section is lost.
+struct SectionsJson<'a>(&'a [SectionOutput]);
+
+impl Serialize for SectionsJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for section in self.0 {
+            let count = seen.entry(section.label.as_str()).or_default();
+            *count += 1;
+            let key = if *count == 1 {
+                section.label.clone()
+            } else {
+                format!("{} ({})", section.label, count)
+            };
+            let record = SectionRecord {
+                stdout: &section.stdout,
+                stderr: &section.stderr,
+                status: section.status,
+                timed_out: section.timed_out,
+                duration_ms: section.duration.as_millis() as u64,
+            };
+            map.serialize_entry(&key, &record)?;
+        }
+        map.end()
+    }
+}
+
+/// Prints the sections of a repository as a JSON object keyed by label, for
+/// editors and dashboards that render the view themselves
+pub fn print_sections_json(vars: &TemplateVars, commands: Option<&[RViewCmd]>) -> RkitResult<()> {
+    let sections = capture_sections(vars, commands)?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&SectionsJson(&sections))?
+    );
+    Ok(())
+}
+
+/// Runs the configured sections and captures their output, for callers that
+/// render the view themselves. Without sections, the README or a directory
+/// listing is returned.
+pub fn capture_sections(
+    vars: &TemplateVars,
+    commands: Option<&[RViewCmd]>,
+) -> RkitResult<Vec<SectionOutput>> {
+    let repo_path = vars.repo();
+    check_repo(repo_path)?;
+
+    let Some(cmds) = commands else {
+        return Ok(vec![fallback_section(repo_path)]);
+    };
+
+    Ok(run_sections(prepare_sections(cmds, vars)))
+}
+
+/// How a configured section produces its output
+enum SectionJob {
+    /// A built-in section of the repository at the given path
+    Builtin(SectionKind, PathBuf),
+    Command(Command),
+}
 
-pub fn view_repo(repo_path: &Path, commands: Option<&[RViewCmd]>) -> RkitResult<()> {
+/// Jobs of the configured sections, skipping sections without a command
+fn prepare_sections<'a>(
+    cmds: &'a [RViewCmd],
+    vars: &TemplateVars,
+) -> Vec<(&'a RViewCmd, RkitResult<SectionJob>)> {
+    cmds.iter()
+        .filter_map(|cmd| {
+            if let Some(kind) = cmd.kind {
+                let job = SectionJob::Builtin(kind, vars.repo().to_path_buf());
+                return Some((cmd, Ok(job)));
+            }
+            match section_command(cmd, vars) {
+                Ok(Some(command)) => Some((cmd, Ok(SectionJob::Command(command)))),
+                Ok(None) => None,
+                Err(e) => Some((cmd, Err(e))),
+            }
+        })
+        .collect()
+}
+
+/// Runs every section at once, each command limited by its own timeout, and
+/// returns their output in configured order
+fn run_sections(sections: Vec<(&RViewCmd, RkitResult<SectionJob>)>) -> Vec<SectionOutput> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = sections
+            .into_iter()
+            .map(|(cmd, command)| scope.spawn(move || run_section(cmd, command)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+fn run_section(cmd: &RViewCmd, job: RkitResult<SectionJob>) -> SectionOutput {
+    let mut section = SectionOutput {
+        label: cmd.label.clone(),
+        ..Default::default()
+    };
+    let mut command = match job {
+        Ok(SectionJob::Command(command)) => command,
+        Ok(SectionJob::Builtin(kind, repo)) => {
+            run_builtin(&mut section, cmd.time_limit(), move || {
+                sections::render(kind, &repo)
+            });
+            return section;
+        }
+        Err(e) => {
+            section.stderr = e.to_string();
+            return section;
+        }
+    };
+
+    log::debug!("Running command for {}: {:?}", cmd.label, command);
+    match output_with_timeout(&mut command, cmd.time_limit()) {
+        Ok(output) => {
+            section.stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            section.stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            section.status = output.status.and_then(|status| status.code());
+            section.timed_out = output.timed_out;
+            section.duration = output.duration;
+        }
+        Err(e) => section.stderr = format!("Failed to run {}: {}", cmd.command_line(), e),
+    }
+    section
+}
+
+/// Renders a built-in section on its own thread, giving up on it once
+/// `timeout` expires. A thread cannot be killed, so an abandoned section
+/// finishes in the background.
+fn run_builtin(
+    section: &mut SectionOutput,
+    timeout: Option<Duration>,
+    render: impl FnOnce() -> RkitResult<String> + Send + 'static,
+) {
+    let start = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(render());
+    });
+    let received = match timeout {
+        Some(limit) => receiver.recv_timeout(limit),
+        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match received {
+        Ok(Ok(text)) => {
+            section.stdout = text;
+            section.status = Some(0);
+        }
+        Ok(Err(e)) => {
+            section.stderr = format!("{}\n", e);
+            section.status = Some(1);
+        }
+        Err(RecvTimeoutError::Timeout) => section.timed_out = true,
+        Err(RecvTimeoutError::Disconnected) => {
+            section.stderr = format!("Failed to render {}\n", section.label);
+        }
+    }
+    section.duration = start.elapsed();
+}
+
+/// Builds the process for a section, or `None` if it has no command.
+/// Placeholders are substituted after the command is split, so a path with
+/// spaces stays a single argument; for shell commands values are quoted
+/// instead.
+fn section_command(cmd: &RViewCmd, vars: &TemplateVars) -> RkitResult<Option<Command>> {
+    let argv: Vec<String> = if !cmd.args.is_empty() {
+        cmd.args.iter().map(|arg| vars.expand(arg)).collect()
+    } else if cmd.is_empty() {
+        Vec::new()
+    } else if cmd.shell {
+        let (shell, flag) = user_shell();
+        let script = vars.expand_with(&cmd.command, shell_quote);
+        vec![shell, flag.to_string(), script]
+    } else {
+        split_command(&cmd.command)
+            .ok_or_else(|| {
+                RkitError::ConfigError(format!(
+                    "Invalid quoting in command for {}: {}",
+                    cmd.label, cmd.command
+                ))
+            })?
+            .iter()
+            .map(|arg| vars.expand(arg))
+            .collect()
+    };
+
+    Ok(argv.split_first().map(|(program, args)| {
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    }))
+}
+
+/// Splits a command into words with the quoting rules of the platform:
+/// POSIX shell rules, or Windows rules where backslashes are path separators
+fn split_command(command: &str) -> Option<Vec<String>> {
+    if cfg!(windows) {
+        split_windows(command)
+    } else {
+        shlex::split(command)
+    }
+}
+
+/// Splits a command the way Windows programs parse their command line: words
+/// are separated by whitespace and grouped with double quotes, `""` inside
+/// quotes is a literal quote and backslashes are kept as they are. `None` if
+/// a quote is left open.
+fn split_windows(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                word.push('"');
+            }
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quoted {
+        return None;
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// The user's shell and the flag that makes it run a command string
+fn user_shell() -> (String, &'static str) {
+    if cfg!(windows) {
+        (
+            env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string()),
+            "/C",
+        )
+    } else {
+        let shell = env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| "/bin/sh".to_string());
+        (shell, "-c")
+    }
+}
+
+/// Quotes a value so the user's shell reads it as a single word
+fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        quote_cmd(value)
+    } else {
+        shlex::try_quote(value)
+            .map(|quoted| quoted.into_owned())
+            .unwrap_or_else(|_| value.to_string())
+    }
+}
+
+/// Quotes a value for `cmd.exe`: embedded quotes are doubled and `%` is
+/// escaped outside the quotes so it does not expand a variable
+fn quote_cmd(value: &str) -> String {
+    let escaped = value.replace('"', "\"\"").replace('%', "\"^%\"");
+    format!("\"{}\"", escaped)
+}
+
+/// Section shown when no `rview` sections are configured: the README if
+/// there is one, otherwise a directory listing
+fn fallback_section(repo_path: &Path) -> SectionOutput {
+    match fs::read_to_string(repo_path.join("README.md")) {
+        Ok(content) => {
+            log::info!("Displaying README for {}", repo_path.display());
+            SectionOutput::text("README", content)
+        }
+        Err(_) => {
+            log::info!("Listing directory contents for {}", repo_path.display());
+            SectionOutput::text("Directory Listing", list_dir(repo_path))
+        }
+    }
+}
+
+/// Names of the entries in a directory, directories marked with a trailing
+/// slash
+fn list_dir(path: &Path) -> String {
+    let mut names: Vec<String> = fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let mut name = entry.file_name().to_string_lossy().to_string();
+                    if entry.path().is_dir() {
+                        name.push('/');
+                    }
+                    name
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.join("\n")
+}
+
+/// Checks that a path is a readable git repository
+fn check_repo(repo_path: &Path) -> RkitResult<()> {
     // Validate repository path
     if !repo_path.exists() {
         log::error!("Repository not found: {}", repo_path.display());
@@ -12,8 +400,7 @@ pub fn view_repo(repo_path: &Path, commands: Option<&[RViewCmd]>) -> RkitResult<
     }
 
     // Check if it's a git repository
-    let git_dir = repo_path.join(".git");
-    if !git_dir.exists() {
+    if RepoKind::detect(repo_path).is_none() {
         log::error!("Not a git repository: {}", repo_path.display());
         return Err(RkitError::InvalidPathError(format!(
             "{} is not a git repository",
@@ -29,89 +416,65 @@ pub fn view_repo(repo_path: &Path, commands: Option<&[RViewCmd]>) -> RkitResult<
             repo_path.display()
         )));
     }
+    Ok(())
+}
 
-    if let Some(cmds) = commands {
-        for cmd in cmds {
-            let command_str = cmd.command.replace("{REPO}", &repo_path.to_string_lossy());
-            let parts: Vec<&str> = command_str.split_whitespace().collect();
+/// Prints a section under a `=== label ===` banner, its stderr going to
+/// stderr
+fn print_section(section: &SectionOutput) -> RkitResult<()> {
+    println!("=== {} ===", section.label);
+    print!("{}", section.stdout);
+    if !section.stdout.is_empty() && !section.stdout.ends_with('\n') {
+        println!();
+    }
+    io::stdout().flush().map_err(RkitError::IoError)?;
+    eprint!("{}", section.stderr);
+    if section.timed_out {
+        println!("{}", section.timed_out_message());
+    }
+    println!(); // Add a blank line between sections
+    Ok(())
+}
 
-            if parts.is_empty() {
-                log::warn!("Empty command for label: {}", cmd.label);
-                continue;
-            }
+/// Prints every section of a repository and records the view as a visit.
+/// Previews render through [`capture_sections`], which records nothing.
+pub fn view_repo(vars: &TemplateVars, commands: Option<&[RViewCmd]>) -> RkitResult<()> {
+    let repo_path = vars.repo();
+    check_repo(repo_path)?;
+    record_visit(&Usage::default_path(), repo_path);
 
-            println!("=== {} ===", cmd.label);
-
-            log::debug!("Running command for {}: {}", cmd.label, command_str);
-            let mut child = Command::new(parts[0])
-                .args(&parts[1..])
-                .stdout(Stdio::inherit())
-                .stderr(Stdio::inherit())
-                .spawn()
-                .map_err(|e| RkitError::ShellCommandError {
-                    command: cmd.command.clone(),
-                    source: e,
-                })?;
-
-            // Wait for the command to complete
-            let status = child.wait().map_err(|e| RkitError::ShellCommandError {
-                command: cmd.command.clone(),
-                source: e,
-            })?;
-
-            if !status.success() {
-                log::warn!("Command '{}' exited with status: {}", cmd.command, status);
-            }
+    let Some(cmds) = commands else {
+        return print_section(&fallback_section(repo_path));
+    };
 
-            println!(); // Add a blank line between commands
-        }
-    } else {
-        // Fallback behavior
-        let readme_path = repo_path.join("README.md");
-        if readme_path.exists() {
-            log::info!("Displaying README for {}", repo_path.display());
-            println!("=== README ===");
-            let content =
-                std::fs::read_to_string(&readme_path).map_err(|e| RkitError::FileReadError {
-                    path: readme_path,
-                    source: e,
-                })?;
-            println!("{}", content);
-        } else {
-            log::info!("Listing directory contents for {}", repo_path.display());
-            println!("=== Directory Listing ===");
-            let child = Command::new("ls")
-                .arg("-la")
-                .arg(repo_path)
-                .stdout(if cfg!(test) {
-                    Stdio::piped()
-                } else {
-                    Stdio::inherit()
-                })
-                .stderr(if cfg!(test) {
-                    Stdio::piped()
-                } else {
-                    Stdio::inherit()
-                })
-                .spawn()
-                .map_err(|e| RkitError::ShellCommandError {
-                    command: format!("ls -la {}", repo_path.display()),
-                    source: e,
-                })?;
-
-            let output = child
-                .wait_with_output()
-                .map_err(|e| RkitError::ShellCommandError {
-                    command: format!("ls -la {}", repo_path.display()),
-                    source: e,
-                })?;
-
-            // In test mode, don't print the output to avoid cluttering test results
-            if !cfg!(test) && output.status.success() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
+    for cmd in cmds.iter().filter(|cmd| cmd.is_empty()) {
+        log::warn!("Empty command for label: {}", cmd.label);
+    }
+
+    let mut sections = Vec::with_capacity(cmds.len());
+    for (cmd, job) in prepare_sections(cmds, vars) {
+        match job {
+            Ok(job) => sections.push((cmd, Ok(job))),
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(e);
             }
         }
     }
+
+    let command_lines: Vec<String> = sections.iter().map(|(cmd, _)| cmd.command_line()).collect();
+    for (command_line, section) in command_lines.iter().zip(run_sections(sections)) {
+        print_section(&section)?;
+        if section.timed_out {
+            log::warn!("Command '{}' timed out", command_line);
+        } else if section.status != Some(0) {
+            log::warn!(
+                "Command '{}' exited with status: {:?}",
+                command_line,
+                section.status
+            );
+        }
+    }
     Ok(())
 }
 
@@ -125,7 +488,7 @@ mod tests {
     fn test_view_repo_not_found() {
         let dir = tempdir().unwrap();
         let missing = dir.path().join("missing");
-        let res = view_repo(&missing, None);
+        let res = view_repo(&TemplateVars::new(&missing), None);
         assert!(matches!(res, Err(RkitError::RepoNotFoundError(_))));
     }
 
@@ -134,7 +497,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let not_git = dir.path().join("not_git");
         fs::create_dir_all(&not_git).unwrap();
-        let res = view_repo(&not_git, None);
+        let res = view_repo(&TemplateVars::new(&not_git), None);
         assert!(matches!(res, Err(RkitError::InvalidPathError(_))));
     }
 
@@ -156,7 +519,7 @@ mod tests {
 
             // Try to set permissions and check if it worked
             if fs::set_permissions(&repo, perms).is_ok() {
-                let res = view_repo(&repo, None);
+                let res = view_repo(&TemplateVars::new(&repo), None);
 
                 // Check if we actually got a permission error
                 let got_permission_error = matches!(res, Err(RkitError::PermissionError(_)));
@@ -174,7 +537,7 @@ mod tests {
                 }
             } else {
                 // If we can't set permissions, just test that the function doesn't crash
-                let res = view_repo(&repo, None);
+                let res = view_repo(&TemplateVars::new(&repo), None);
                 assert!(res.is_ok());
             }
         }
@@ -188,7 +551,7 @@ mod tests {
             // Create .git directory elsewhere so it exists
             fs::create_dir_all(dir.path().join("repo_git")).unwrap();
 
-            let res = view_repo(&repo, None);
+            let res = view_repo(&TemplateVars::new(&repo), None);
             assert!(res.is_err()); // Less specific for non-Unix systems
         }
     }
@@ -200,7 +563,7 @@ mod tests {
         fs::create_dir_all(repo.join(".git")).unwrap();
         let readme = repo.join("README.md");
         fs::write(&readme, "Hello").unwrap();
-        let res = view_repo(&repo, None);
+        let res = view_repo(&TemplateVars::new(&repo), None);
         assert!(res.is_ok());
     }
 
@@ -210,7 +573,231 @@ mod tests {
         let repo = dir.path().join("repo");
         fs::create_dir_all(repo.join(".git")).unwrap();
         // No README.md file, should fall back to directory listing
-        let res = view_repo(&repo, None);
+        let res = view_repo(&TemplateVars::new(&repo), None);
         assert!(res.is_ok());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_sections() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        let sections = capture_sections(&TemplateVars::new(&repo), None).unwrap();
+        assert_eq!(sections[0].label, "Directory Listing");
+        assert_eq!(sections[0].output(), ".git/");
+
+        let commands = vec![
+            RViewCmd {
+                command: "echo {REPO}".to_string(),
+                label: "Path".to_string(),
+                ..Default::default()
+            },
+            RViewCmd {
+                command: "ls {REPO}/missing".to_string(),
+                label: "Missing".to_string(),
+                ..Default::default()
+            },
+        ];
+        let sections = capture_sections(&TemplateVars::new(&repo), Some(&commands)).unwrap();
+        assert_eq!(sections[0].output().trim(), repo.to_string_lossy());
+        // stderr is captured too
+        assert!(!sections[1].output().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_sections_parallel_with_timeout() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        let section = |label: &str, command: &str, timeout: Option<f64>| RViewCmd {
+            command: command.to_string(),
+            label: label.to_string(),
+            shell: true,
+            timeout,
+            ..Default::default()
+        };
+        // Each of the outer sections waits for a file the other creates, so
+        // they only finish if they run at the same time
+        let handshake = |create: &str, wait: &str, output: &str| {
+            format!(
+                "touch {create}; while [ ! -f {wait} ]; do sleep 0.05; done; echo {output}",
+                create = dir.path().join(create).display(),
+                wait = dir.path().join(wait).display(),
+                output = output,
+            )
+        };
+        let commands = vec![
+            section("First", &handshake("first", "second", "first"), Some(30.0)),
+            section("Stuck", "echo partial; sleep 5", Some(0.3)),
+            section(
+                "Second",
+                &handshake("second", "first", "second"),
+                Some(30.0),
+            ),
+        ];
+
+        let sections = capture_sections(&TemplateVars::new(&repo), Some(&commands)).unwrap();
+
+        let labels: Vec<&str> = sections.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, ["First", "Stuck", "Second"]);
+        assert!(!sections[0].timed_out);
+        assert_eq!(sections[0].stdout, "first\n");
+        assert_eq!(sections[0].status, Some(0));
+        assert!(sections[1].timed_out);
+        assert_eq!(sections[1].status, None);
+        assert!(sections[1].output().contains("[timed out after"));
+        assert!(!sections[2].timed_out);
+        assert_eq!(sections[2].stdout, "second\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sections_json() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        let section = |label: &str, command: &str| RViewCmd {
+            command: command.to_string(),
+            label: label.to_string(),
+            shell: true,
+            ..Default::default()
+        };
+        let commands = vec![
+            section("Zeta", "echo out; echo err >&2; exit 3"),
+            section("Alpha", "printf ok"),
+            section("Zeta", "true"),
+        ];
+        let sections = capture_sections(&TemplateVars::new(&repo), Some(&commands)).unwrap();
+        let json = serde_json::to_string(&SectionsJson(&sections)).unwrap();
+
+        // Keys keep the configured order instead of being sorted
+        let zeta = json.find("\"Zeta\"").unwrap();
+        let alpha = json.find("\"Alpha\"").unwrap();
+        assert!(zeta < alpha && alpha < json.find("\"Zeta (2)\"").unwrap());
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["Zeta"]["stdout"], "out\n");
+        assert_eq!(value["Zeta"]["stderr"], "err\n");
+        assert_eq!(value["Zeta"]["status"], 3);
+        assert_eq!(value["Zeta"]["timed_out"], false);
+        assert!(value["Zeta"]["duration_ms"].is_u64());
+        assert_eq!(value["Alpha"]["stdout"], "ok");
+        assert_eq!(value["Zeta (2)"]["status"], 0);
+    }
+
+    #[test]
+    fn test_run_builtin_timeout() {
+        let mut section = SectionOutput::default();
+        run_builtin(&mut section, Some(Duration::from_millis(100)), || {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(String::new())
+        });
+        assert!(section.timed_out);
+        assert_eq!(section.status, None);
+        assert!(section.duration < Duration::from_secs(5));
+
+        let mut section = SectionOutput::default();
+        run_builtin(&mut section, Some(Duration::from_secs(5)), || {
+            Ok("main\n".to_string())
+        });
+        assert!(!section.timed_out);
+        assert_eq!(
+            (section.stdout.as_str(), section.status),
+            ("main\n", Some(0))
+        );
+    }
+
+    #[test]
+    fn test_windows_quoting() {
+        assert_eq!(
+            split_windows(r#"type {REPO}\README.md"#).unwrap(),
+            ["type", r"{REPO}\README.md"]
+        );
+        assert_eq!(
+            split_windows(r#""C:\Program Files\x.exe"  -m "say ""hi""" C:\tools\"#).unwrap(),
+            [r"C:\Program Files\x.exe", "-m", r#"say "hi""#, r"C:\tools\"]
+        );
+        assert_eq!(split_windows(r#"echo """#).unwrap(), ["echo", ""]);
+        assert!(split_windows(r#"echo "open"#).is_none());
+
+        assert_eq!(quote_cmd(r"C:\my repo"), r#""C:\my repo""#);
+        assert_eq!(quote_cmd(r#"a"b%PATH%"#), r#""a""b"^%"PATH"^%"""#);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_section_command_windows_path() {
+        let vars = TemplateVars::new(Path::new(r"C:\projects\my repo"));
+        let cmd = RViewCmd {
+            command: r"C:\tools\cat.exe {REPO}\README.md".to_string(),
+            ..Default::default()
+        };
+        let command = section_command(&cmd, &vars).unwrap().unwrap();
+        assert_eq!(command.get_program(), r"C:\tools\cat.exe");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, [r"C:\projects\my repo\README.md"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_section_command_quoting() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("my repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join("notes.txt"), "one\ntwo\n").unwrap();
+
+        let section = |cmd: RViewCmd| {
+            let sections =
+                capture_sections(&TemplateVars::new(&repo), Some(std::slice::from_ref(&cmd)))
+                    .unwrap();
+            sections[0].output()
+        };
+        let expected = format!("{}/notes.txt\n", repo.display());
+
+        let quoted = RViewCmd {
+            command: r#"printf "%s\n" "{REPO}/notes.txt""#.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(section(quoted), expected);
+
+        let unquoted = RViewCmd {
+            command: "printf %s {REPO}/notes.txt".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(section(unquoted), expected.trim_end());
+
+        let args = RViewCmd {
+            args: vec!["cat".to_string(), "{REPO}/notes.txt".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(section(args), "one\ntwo\n");
+
+        let shell = RViewCmd {
+            command: "cat {REPO}/notes.txt | wc -l".to_string(),
+            shell: true,
+            ..Default::default()
+        };
+        assert_eq!(section(shell).trim(), "2");
+
+        let invalid = RViewCmd {
+            command: r#"echo "unterminated"#.to_string(),
+            ..Default::default()
+        };
+        assert!(section(invalid).contains("Invalid quoting"));
+        assert!(matches!(
+            view_repo(
+                &TemplateVars::new(&repo),
+                Some(&[RViewCmd {
+                    command: r#"echo "unterminated"#.to_string(),
+                    ..Default::default()
+                }])
+            ),
+            Err(RkitError::ConfigError(_))
+        ));
+    }
 }
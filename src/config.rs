@@ -1,18 +1,251 @@
 use crate::error::{RkitError, RkitResult};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Built-in `rview` sections, rendered by rkit from the repository's files
+/// and `.git` directory without running git
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionKind {
+    Branch,
+    Status,
+    Reflog,
+    Readme,
+    Remotes,
+    Languages,
+    Size,
+}
+
+impl SectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Branch => "branch",
+            SectionKind::Status => "status",
+            SectionKind::Reflog => "reflog",
+            SectionKind::Readme => "readme",
+            SectionKind::Remotes => "remotes",
+            SectionKind::Languages => "languages",
+            SectionKind::Size => "size",
+        }
+    }
+}
+
+/// A section of `rkit view`: either a built-in `type`, or a command given as
+/// a string split with shell quoting rules, an explicit `args` list, or with
+/// `shell` set, a string run by the user's shell.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RViewCmd {
+    /// Built-in section, used instead of a command
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SectionKind>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
+    /// Program and arguments, used instead of `command`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Run `command` through the user's shell, allowing pipes and redirects
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shell: bool,
     pub label: String,
+    /// Seconds the command may run before it is killed, fractions allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+}
+
+impl RViewCmd {
+    /// The command as configured, for logs and error messages
+    pub fn command_line(&self) -> String {
+        if let Some(kind) = self.kind {
+            format!("type: {}", kind.name())
+        } else if self.args.is_empty() {
+            self.command.clone()
+        } else {
+            self.args.join(" ")
+        }
+    }
+
+    /// Whether neither a built-in type, a command nor arguments are configured
+    pub fn is_empty(&self) -> bool {
+        self.kind.is_none() && self.args.is_empty() && self.command.trim().is_empty()
+    }
+
+    /// The configured timeout, or `None` when unset or not positive
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.timeout
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .filter(|limit| !limit.is_zero())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Transport used when expanding shorthand repository references
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Https,
+    Ssh,
+}
+
+impl Transport {
+    fn is_https(&self) -> bool {
+        *self == Transport::Https
+    }
+}
+
+/// A short name for a git host, e.g. `gh` for `github.com`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostAlias {
+    pub host: String,
+    #[serde(default)]
+    pub transport: Transport,
+    /// SSH user (defaults to `git`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// Flags passed through to `git clone`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloneFlags {
+    /// Create a shallow clone with this many commits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    /// Branch to check out instead of the remote's HEAD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub single_branch: bool,
+    /// Partial clone filter, e.g. `blob:none`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub recurse_submodules: bool,
+    #[serde(default)]
+    pub bare: bool,
+    #[serde(default)]
+    pub mirror: bool,
+}
+
+impl CloneFlags {
+    /// Combines these flags with `defaults`, preferring values set here
+    pub fn or(&self, defaults: &CloneFlags) -> CloneFlags {
+        CloneFlags {
+            depth: self.depth.or(defaults.depth),
+            branch: self.branch.clone().or_else(|| defaults.branch.clone()),
+            single_branch: self.single_branch || defaults.single_branch,
+            filter: self.filter.clone().or_else(|| defaults.filter.clone()),
+            recurse_submodules: self.recurse_submodules || defaults.recurse_submodules,
+            // --mirror implies --bare, so either one set here wins over both
+            bare: self.bare || (!self.mirror && defaults.bare),
+            mirror: self.mirror || (!self.bare && defaults.mirror),
+        }
+    }
+
+    /// Arguments for `git clone`, placed before the URL
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(depth) = self.depth {
+            args.push(format!("--depth={}", depth));
+        }
+        if let Some(branch) = &self.branch {
+            args.push(format!("--branch={}", branch));
+        }
+        if self.single_branch {
+            args.push("--single-branch".to_string());
+        }
+        if let Some(filter) = &self.filter {
+            args.push(format!("--filter={}", filter));
+        }
+        if self.recurse_submodules {
+            args.push("--recurse-submodules".to_string());
+        }
+        if self.mirror {
+            args.push("--mirror".to_string());
+        } else if self.bare {
+            args.push("--bare".to_string());
+        }
+        args
+    }
+}
+
+/// Name of the root used when only `project_root` is configured
+pub const DEFAULT_ROOT: &str = "default";
+
+/// A named directory holding repositories. Walker settings given here are
+/// used when scanning this root unless overridden on the command line.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RootConfig {
+    pub name: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_links: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_file_system: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_repos: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_at_git: Option<bool>,
+    /// Exclude globs applied to this root on top of the global `excludes`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+}
+
+/// Settings that apply to repositories from a single domain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DomainConfig {
+    /// Layout overriding the global `layout` for this domain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Default `git clone` flags for this domain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone: Option<CloneFlags>,
+    /// Name of the root repositories from this domain are cloned into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// Directory holding repositories, used when `roots` is empty
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub project_root: String,
+    /// Named directories holding repositories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<RootConfig>,
     pub rview: Option<Vec<RViewCmd>>,
+    /// Host used to expand `org/repo` shorthand in `rkit clone`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_host: Option<String>,
+    /// Transport used with `default_host`
+    #[serde(default, skip_serializing_if = "Transport::is_https")]
+    pub default_transport: Transport,
+    /// Host aliases usable as `alias:org/repo` in `rkit clone`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub aliases: HashMap<String, HostAlias>,
+    /// Template for where repositories are placed under `project_root`, e.g.
+    /// `{domain}/{org}/{repo}` (the default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Per-domain settings, keyed by host name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub domains: HashMap<String, DomainConfig>,
+    /// Gitignore-style globs of paths never scanned for repositories, e.g.
+    /// `node_modules` or `/archive`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    /// Worker threads for scanning every root and for commands spanning the
+    /// whole workspace, such as `fetch` and `status`. Defaults to the number
+    /// of CPU cores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
 }
 
 impl Config {
@@ -71,31 +304,168 @@ impl Config {
     }
 
     pub fn expand_project_root(&self) -> RkitResult<PathBuf> {
-        let expanded = if cfg!(windows) {
-            // On Windows, expand %USERPROFILE% environment variable
-            std::env::var("USERPROFILE")
-                .map_err(|_| RkitError::EnvVarError("USERPROFILE".to_string()))?
-                + &self.project_root.replace("%USERPROFILE%", "")
+        expand_path(&self.project_root)
+    }
+
+    /// Every configured project root with its path expanded. Without a
+    /// `roots` list, `project_root` is used as a single root named `default`.
+    pub fn project_roots(&self) -> RkitResult<Vec<(RootConfig, PathBuf)>> {
+        if self.roots.is_empty() {
+            if self.project_root.is_empty() {
+                return Err(RkitError::ConfigError(
+                    "Either project_root or roots must be configured".to_string(),
+                ));
+            }
+            let root = RootConfig {
+                name: DEFAULT_ROOT.to_string(),
+                path: self.project_root.clone(),
+                ..Default::default()
+            };
+            let path = self.expand_project_root()?;
+            return Ok(vec![(root, path)]);
+        }
+
+        let mut roots: Vec<(RootConfig, PathBuf)> = Vec::with_capacity(self.roots.len());
+        for root in &self.roots {
+            if roots.iter().any(|(r, _)| r.name == root.name) {
+                return Err(RkitError::ConfigError(format!(
+                    "Duplicate root name '{}'",
+                    root.name
+                )));
+            }
+            roots.push((root.clone(), expand_path(&root.path)?));
+        }
+        Ok(roots)
+    }
+}
+
+/// Expands `~` (or `%USERPROFILE%` on Windows) in a configured path
+fn expand_path(path: &str) -> RkitResult<PathBuf> {
+    let expanded = if cfg!(windows) {
+        // On Windows, expand %USERPROFILE% environment variable
+        std::env::var("USERPROFILE")
+            .map_err(|_| RkitError::EnvVarError("USERPROFILE".to_string()))?
+            + &path.replace("%USERPROFILE%", "")
+    } else {
+        // On Unix-like systems, expand ~
+        if let Some(rest) = path.strip_prefix("~/") {
+            let home = dirs::home_dir().ok_or_else(|| {
+                RkitError::ConfigError("Could not find home directory".to_string())
+            })?;
+            home.join(rest).to_string_lossy().to_string()
+        } else if path == "~" {
+            dirs::home_dir()
+                .ok_or_else(|| RkitError::ConfigError("Could not find home directory".to_string()))?
+                .to_string_lossy()
+                .to_string()
         } else {
-            // On Unix-like systems, expand ~
-            if self.project_root.starts_with("~/") {
-                let home = dirs::home_dir().ok_or_else(|| {
-                    RkitError::ConfigError("Could not find home directory".to_string())
-                })?;
-                home.join(&self.project_root[2..])
-                    .to_string_lossy()
-                    .to_string()
-            } else if self.project_root == "~" {
-                dirs::home_dir()
-                    .ok_or_else(|| {
-                        RkitError::ConfigError("Could not find home directory".to_string())
-                    })?
-                    .to_string_lossy()
-                    .to_string()
-            } else {
-                self.project_root.clone()
+            path.to_string()
+        }
+    };
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_roots() {
+        let config: Config = serde_yml::from_str("project_root: /srv/projects\nrview:\n").unwrap();
+        let roots = config.project_roots().unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].0.name, DEFAULT_ROOT);
+        assert_eq!(roots[0].1, PathBuf::from("/srv/projects"));
+
+        let yaml = r#"
+rview:
+roots:
+  - name: work
+    path: /nvme/work
+    max_depth: 3
+  - name: archive
+    path: /data/archive
+    stop_at_git: false
+    excludes: [old]
+excludes: [node_modules, target]
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let roots = config.project_roots().unwrap();
+        assert_eq!(roots[0].0.name, "work");
+        assert_eq!(roots[0].0.max_depth, Some(3));
+        assert_eq!(roots[1].1, PathBuf::from("/data/archive"));
+        assert_eq!(roots[1].0.stop_at_git, Some(false));
+        assert_eq!(roots[1].0.excludes, vec!["old"]);
+        assert_eq!(config.excludes, vec!["node_modules", "target"]);
+    }
+
+    #[test]
+    fn test_project_roots_invalid() {
+        let config: Config = serde_yml::from_str("rview:\n").unwrap();
+        assert!(config.project_roots().is_err());
+
+        let yaml = r#"
+rview:
+roots:
+  - name: work
+    path: /a
+  - name: work
+    path: /b
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        assert!(config.project_roots().is_err());
+    }
+
+    #[test]
+    fn test_rview_forms() {
+        let yaml = r#"
+rview:
+  - command: basename {REPO}
+    label: Repo
+  - args: [git, -C, "{REPO}", log, "--format=%h %s"]
+    label: Log
+  - command: git -C {REPO} log --oneline | head -5
+    shell: true
+    label: Recent
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let rview = config.rview.unwrap();
+        assert!(rview[0].args.is_empty() && !rview[0].shell);
+        assert_eq!(rview[1].args.len(), 5);
+        assert_eq!(rview[1].command_line(), "git -C {REPO} log --format=%h %s");
+        assert!(rview[2].shell);
+        assert_eq!(rview[0].time_limit(), None);
+
+        let cmd: RViewCmd = serde_yml::from_str("{type: branch, label: Branch}").unwrap();
+        assert_eq!(cmd.kind, Some(SectionKind::Branch));
+        assert!(!cmd.is_empty());
+        assert_eq!(cmd.command_line(), "type: branch");
+        assert!(serde_yml::from_str::<RViewCmd>("{type: weather, label: W}").is_err());
+
+        let cmd: RViewCmd =
+            serde_yml::from_str("{command: git status, label: S, timeout: 0.5}").unwrap();
+        assert_eq!(cmd.time_limit(), Some(Duration::from_millis(500)));
+        let cmd: RViewCmd =
+            serde_yml::from_str("{command: git status, label: S, timeout: 2}").unwrap();
+        assert_eq!(cmd.time_limit(), Some(Duration::from_secs(2)));
+        let cmd: RViewCmd =
+            serde_yml::from_str("{command: git status, label: S, timeout: -1}").unwrap();
+        assert_eq!(cmd.time_limit(), None);
+    }
+
+    #[test]
+    fn test_default_configs() {
+        for yaml in [
+            include_str!("../etc/default_config_linux.yaml"),
+            include_str!("../etc/default_config_windows.yaml"),
+        ] {
+            let config: Config = serde_yml::from_str(yaml).unwrap();
+            // Placeholders must be whole arguments, never spliced into quoted
+            // strings that a path with quotes in it would break
+            for cmd in config.rview.unwrap() {
+                assert!(!cmd.command.contains("'{"), "{}", cmd.command);
+                assert!(!cmd.command.contains("\"{"), "{}", cmd.command);
             }
-        };
-        Ok(PathBuf::from(expanded))
+        }
     }
 }
@@ -45,6 +45,15 @@ pub enum RkitError {
         source: std::io::Error,
     },
 
+    #[error("Command failed in {failed} of {total} repositories")]
+    ExecError { failed: usize, total: usize },
+
+    #[error("Target directory already exists: {path} ({reason})")]
+    TargetExistsError {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+
     #[error("Repository not found: {0}")]
     RepoNotFoundError(std::path::PathBuf),
 
@@ -0,0 +1,328 @@
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::error::{RkitError, RkitResult};
+use crate::layout::Layouts;
+
+/// Selects repositories by the domain and organization derived from their
+/// path through the configured layouts and by glob patterns matched against
+/// their relative path. Repositories can also be selected by the files they
+/// contain and by how recently they were used. An empty filter matches every
+/// repository.
+#[derive(Debug, Clone, Default)]
+pub struct RepoFilter {
+    domains: Vec<String>,
+    orgs: Vec<String>,
+    globs: Option<GlobSet>,
+    pattern: Option<GlobMatcher>,
+    regex: Option<Regex>,
+    has_files: Vec<PathBuf>,
+    modified_since: Option<SystemTime>,
+    layouts: Layouts,
+}
+
+impl RepoFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match repositories under one of the given domains
+    pub fn domains(mut self, domains: Vec<String>) -> Self {
+        self.domains = domains;
+        self
+    }
+
+    /// Only match repositories in one of the given organizations. Nested
+    /// groups match their parent, so `group` also matches `group/subgroup`.
+    pub fn orgs(mut self, orgs: Vec<String>) -> Self {
+        self.orgs = orgs;
+        self
+    }
+
+    /// Layouts used to derive the domain and organization of a path
+    pub fn layouts(mut self, layouts: Layouts) -> Self {
+        self.layouts = layouts;
+        self
+    }
+
+    /// Only match repositories whose relative path matches one of the globs
+    pub fn globs(mut self, patterns: &[String]) -> RkitResult<Self> {
+        if patterns.is_empty() {
+            self.globs = None;
+            return Ok(self);
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| {
+                RkitError::ConfigError(format!("Invalid glob pattern '{}': {}", pattern, e))
+            })?;
+            builder.add(glob);
+        }
+        self.globs = Some(builder.build().map_err(|e| {
+            RkitError::ConfigError(format!("Failed to build glob patterns: {}", e))
+        })?);
+        Ok(self)
+    }
+
+    /// Only match repositories whose relative path matches the glob, or
+    /// contains a match of the regular expression when `regex` is set. Unlike
+    /// `globs`, this applies on top of every other condition.
+    pub fn pattern(mut self, pattern: &str, regex: bool) -> RkitResult<Self> {
+        if regex {
+            self.regex = Some(Regex::new(pattern).map_err(|e| {
+                RkitError::ConfigError(format!("Invalid regex pattern '{}': {}", pattern, e))
+            })?);
+        } else {
+            let glob = Glob::new(pattern).map_err(|e| {
+                RkitError::ConfigError(format!("Invalid glob pattern '{}': {}", pattern, e))
+            })?;
+            self.pattern = Some(glob.compile_matcher());
+        }
+        Ok(self)
+    }
+
+    /// Only match repositories containing every one of the given paths
+    pub fn has_files(mut self, files: Vec<PathBuf>) -> Self {
+        self.has_files = files;
+        self
+    }
+
+    /// Only match repositories used within `age` of now
+    pub fn modified_within(mut self, age: Option<Duration>) -> Self {
+        self.modified_since = age.and_then(|age| SystemTime::now().checked_sub(age));
+        self
+    }
+
+    /// Whether the filter has no conditions
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+            && self.orgs.is_empty()
+            && self.globs.is_none()
+            && self.pattern.is_none()
+            && self.regex.is_none()
+            && self.has_files.is_empty()
+            && self.modified_since.is_none()
+    }
+
+    /// Checks a repository by its path relative to the project root and by
+    /// its contents on disk
+    pub fn matches_repo(&self, path: &Path, relative_path: &Path) -> bool {
+        if !self.matches(relative_path) {
+            return false;
+        }
+        if !self.has_files.iter().all(|file| path.join(file).exists()) {
+            return false;
+        }
+        match self.modified_since {
+            Some(since) => last_activity(path).is_some_and(|modified| modified >= since),
+            None => true,
+        }
+    }
+
+    /// Checks a repository path relative to the project root, ignoring the
+    /// conditions that need to look at the repository on disk
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        if let Some(globs) = &self.globs {
+            if !globs.is_match(relative_path) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(relative_path) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&relative_path.to_string_lossy()) {
+                return false;
+            }
+        }
+
+        if self.domains.is_empty() && self.orgs.is_empty() {
+            return true;
+        }
+
+        let Some(parsed) = self.layouts.extract(relative_path) else {
+            return false;
+        };
+
+        let domain_matches = self.domains.is_empty()
+            || parsed
+                .domain
+                .as_deref()
+                .is_some_and(|domain| self.domains.iter().any(|d| d.eq_ignore_ascii_case(domain)));
+        let org_matches = self.orgs.is_empty()
+            || parsed.org.as_deref().is_some_and(|parsed_org| {
+                self.orgs.iter().any(|org| {
+                    parsed_org == org
+                        || parsed_org
+                            .strip_prefix(org.as_str())
+                            .is_some_and(|rest| rest.starts_with('/'))
+                })
+            });
+
+        domain_matches && org_matches
+    }
+}
+
+/// Most recent modification time of a repository directory and the git files
+/// touched by commits, checkouts, staging and fetches
+fn last_activity(path: &Path) -> Option<SystemTime> {
+    const MARKERS: [&str; 8] = [
+        "",
+        ".git",
+        ".git/index",
+        ".git/HEAD",
+        ".git/logs/HEAD",
+        ".git/FETCH_HEAD",
+        "HEAD",
+        "FETCH_HEAD",
+    ];
+    MARKERS
+        .iter()
+        .filter_map(|marker| fs::metadata(path.join(marker)).ok())
+        .filter_map(|metadata| metadata.modified().ok())
+        .max()
+}
+
+/// Parses an age such as `90s`, `30m`, `12h`, `7d` or `2w`
+pub fn parse_age(age: &str) -> RkitResult<Duration> {
+    let invalid = || {
+        RkitError::ConfigError(format!(
+            "Invalid age '{}', expected a number followed by s, m, h, d or w",
+            age
+        ))
+    };
+    let split = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (count, unit) = age.split_at(split);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let secs = count
+        .checked_mul(seconds)
+        .ok_or_else(|| RkitError::ConfigError(format!("Age '{}' is too large", age)))?;
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = RepoFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.matches(Path::new("scratch")));
+        assert!(filter.matches(Path::new("github.com/org/repo")));
+    }
+
+    #[test]
+    fn test_domain_and_org_filter() {
+        let filter = RepoFilter::new()
+            .domains(vec!["gitlab.com".to_string()])
+            .orgs(vec!["group".to_string()]);
+        assert!(filter.matches(Path::new("gitlab.com/group/repo")));
+        assert!(filter.matches(Path::new("gitlab.com/group/sub/repo")));
+        assert!(!filter.matches(Path::new("gitlab.com/groupie/repo")));
+        assert!(!filter.matches(Path::new("github.com/group/repo")));
+        assert!(!filter.matches(Path::new("scratch")));
+    }
+
+    #[test]
+    fn test_filter_uses_layouts() {
+        let layouts = Layouts::default().with_domain(
+            "git.corp.example.com",
+            Layout::parse("work/{org}/{repo}").unwrap(),
+        );
+        let filter = RepoFilter::new()
+            .domains(vec!["git.corp.example.com".to_string()])
+            .orgs(vec!["team".to_string()])
+            .layouts(layouts);
+        assert!(filter.matches(Path::new("work/team/service")));
+        assert!(!filter.matches(Path::new("github.com/team/service")));
+    }
+
+    #[test]
+    fn test_glob_filter() {
+        let filter = RepoFilter::new()
+            .globs(&["github.com/imthor/*".to_string()])
+            .unwrap();
+        assert!(filter.matches(Path::new("github.com/imthor/rkit")));
+        assert!(!filter.matches(Path::new("github.com/other/rkit")));
+    }
+
+    #[test]
+    fn test_pattern_filter() {
+        let filter = RepoFilter::new()
+            .pattern("^github\\.com/.*-(cli|api)$", true)
+            .unwrap();
+        assert!(!filter.is_empty());
+        assert!(filter.matches(Path::new("github.com/org/tool-cli")));
+        assert!(!filter.matches(Path::new("gitlab.com/org/tool-cli")));
+        assert!(RepoFilter::new().pattern("(", true).is_err());
+
+        // A glob pattern must match together with the `globs`
+        let filter = RepoFilter::new()
+            .globs(&["github.com/**".to_string()])
+            .unwrap()
+            .pattern("*rkit*", false)
+            .unwrap();
+        assert!(filter.matches(Path::new("github.com/imthor/rkit")));
+        assert!(!filter.matches(Path::new("github.com/imthor/other")));
+        assert!(!filter.matches(Path::new("gitlab.com/imthor/rkit")));
+        assert!(RepoFilter::new().pattern("a/[", false).is_err());
+    }
+
+    #[test]
+    fn test_has_files_and_modified_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let rust = dir.path().join("rust");
+        let other = dir.path().join("other");
+        fs::create_dir_all(rust.join(".git")).unwrap();
+        fs::create_dir_all(other.join(".git")).unwrap();
+        fs::write(rust.join("Cargo.toml"), "").unwrap();
+
+        let filter = RepoFilter::new().has_files(vec![PathBuf::from("Cargo.toml")]);
+        assert!(filter.matches_repo(&rust, Path::new("rust")));
+        assert!(!filter.matches_repo(&other, Path::new("other")));
+
+        let filter = RepoFilter::new().modified_within(Some(Duration::from_secs(3600)));
+        assert!(filter.matches_repo(&rust, Path::new("rust")));
+        let filter = RepoFilter::new().modified_within(Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!filter.matches_repo(&rust, Path::new("rust")));
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert!(matches!(
+            parse_age("99999999999999999w"),
+            Err(RkitError::ConfigError(_))
+        ));
+        for invalid in ["", "7", "d", "7y", "-1d", "1.5h"] {
+            assert!(parse_age(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let result = RepoFilter::new().globs(&["a/[".to_string()]);
+        assert!(matches!(result, Err(RkitError::ConfigError(_))));
+    }
+}
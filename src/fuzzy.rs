@@ -0,0 +1,173 @@
+/// A successful fuzzy match of a query against a candidate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i64,
+    /// Character indices of the candidate that matched, in order
+    pub positions: Vec<usize>,
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 10;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_LAST_SEGMENT: i64 = 20;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP: i64 = 1;
+
+/// Matches a whitespace-separated query against a candidate. Every term must
+/// appear in order as a subsequence of the candidate; terms are matched
+/// case-insensitively unless they contain an uppercase letter. Returns `None`
+/// when some term does not match.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut positions = Vec::new();
+
+    for term in query.split_whitespace() {
+        let term_match = match_term(term, &chars)?;
+        score += term_match.score;
+        positions.extend(term_match.positions);
+    }
+
+    positions.sort_unstable();
+    positions.dedup();
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Best match of a single term, preferring a match inside the last path
+/// segment, which usually holds the repository name
+fn match_term(term: &str, chars: &[char]) -> Option<FuzzyMatch> {
+    let case_sensitive = term.chars().any(char::is_uppercase);
+    let pattern: Vec<char> = term
+        .chars()
+        .map(|c| if case_sensitive { c } else { lower(c) })
+        .collect();
+    let normalized: Vec<char> = chars
+        .iter()
+        .map(|&c| if case_sensitive { c } else { lower(c) })
+        .collect();
+
+    let whole = align(&pattern, &normalized, 0).map(|positions| score(&positions, chars));
+
+    let last_segment = chars
+        .iter()
+        .rposition(|&c| c == '/' || c == '\\')
+        .map(|i| i + 1)
+        .filter(|&start| start > 0 && start < chars.len());
+    let in_last_segment = last_segment
+        .and_then(|start| align(&pattern, &normalized, start))
+        .map(|positions| {
+            let mut found = score(&positions, chars);
+            found.score += BONUS_LAST_SEGMENT;
+            found
+        });
+
+    match (whole, in_last_segment) {
+        (Some(a), Some(b)) if b.score >= a.score => Some(b),
+        (Some(a), _) => Some(a),
+        (None, b) => b,
+    }
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Finds the pattern as a subsequence starting at `from`: a forward pass
+/// finds the earliest end of a match, then a backward pass from that end
+/// finds the shortest window, so matched characters are as close as possible
+fn align(pattern: &[char], text: &[char], from: usize) -> Option<Vec<usize>> {
+    if pattern.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let mut p = 0;
+    let mut end = None;
+    for (i, &c) in text.iter().enumerate().skip(from) {
+        if c == pattern[p] {
+            p += 1;
+            if p == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut p = pattern.len();
+    for i in (from..=end).rev() {
+        if text[i] == pattern[p - 1] {
+            positions.push(i);
+            p -= 1;
+            if p == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn is_boundary(chars: &[char], index: usize) -> bool {
+    index == 0 || matches!(chars[index - 1], '/' | '\\' | '-' | '_' | '.' | ' ')
+}
+
+fn score(positions: &[usize], chars: &[char]) -> FuzzyMatch {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &pos in positions {
+        score += SCORE_MATCH;
+        if is_boundary(chars, pos) {
+            score += BONUS_BOUNDARY;
+        }
+        if let Some(prev) = previous {
+            let gap = (pos - prev - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score -= PENALTY_GAP_START + gap * PENALTY_GAP;
+            }
+        }
+        previous = Some(pos);
+    }
+    FuzzyMatch {
+        score,
+        positions: positions.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence_match() {
+        let found = fuzzy_match("rkt", "github.com/imthor/rkit").unwrap();
+        assert_eq!(found.positions, vec![18, 19, 21]);
+        assert!(fuzzy_match("xyz", "github.com/imthor/rkit").is_none());
+        assert!(fuzzy_match("", "anything").is_some());
+    }
+
+    #[test]
+    fn test_smart_case() {
+        assert!(fuzzy_match("RKIT", "github.com/imthor/rkit").is_none());
+        assert!(fuzzy_match("rkit", "github.com/imthor/RKit").is_some());
+        assert!(fuzzy_match("RK", "github.com/imthor/RKit").is_some());
+    }
+
+    #[test]
+    fn test_multiple_terms() {
+        assert!(fuzzy_match("lab api", "gitlab.com/team/api").is_some());
+        assert!(fuzzy_match("hub api", "gitlab.com/team/api").is_none());
+    }
+
+    #[test]
+    fn test_ranking() {
+        let score = |candidate| fuzzy_match("api", candidate).unwrap().score;
+        // The repository name beats an organization with the same letters
+        assert!(score("github.com/team/api") > score("github.com/api/tools"));
+        // Consecutive letters beat scattered ones
+        assert!(score("github.com/org/api") > score("github.com/org/a-p-i"));
+    }
+}
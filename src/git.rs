@@ -0,0 +1,507 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Git directory of a repository: its `.git` directory, the directory a
+/// `.git` file points at, or the repository itself when it is bare
+pub fn git_dir(repo: &Path) -> Option<PathBuf> {
+    let dot_git = repo.join(".git");
+    match fs::metadata(&dot_git) {
+        Ok(metadata) if metadata.is_dir() => Some(dot_git),
+        Ok(_) => read_gitlink(repo, &dot_git),
+        Err(_) => repo.join("HEAD").is_file().then(|| repo.to_path_buf()),
+    }
+}
+
+/// Target of a `.git` file, which holds a `gitdir: <path>` line relative to
+/// the working tree
+pub(crate) fn read_gitlink(repo: &Path, dot_git: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(dot_git).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("gitdir:"))
+        .map(str::trim)
+        .filter(|gitdir| !gitdir.is_empty())
+        .map(|gitdir| repo.join(gitdir))
+}
+
+/// Directory holding the refs, objects and config shared by every worktree
+/// of a repository
+pub fn common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+/// What `HEAD` points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A branch, by its short name
+    Branch(String),
+    /// A commit, when HEAD is detached
+    Detached(String),
+}
+
+/// Reads `HEAD` from a git directory
+pub fn read_head(git_dir: &Path) -> Option<Head> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(Head::Branch(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            ))
+        }
+        None => Some(Head::Detached(head.to_string())),
+    }
+}
+
+/// Checked out branch, or the abbreviated commit when HEAD is detached
+pub fn head_branch(repo: &Path) -> Option<String> {
+    match read_head(&git_dir(repo)?)? {
+        Head::Branch(branch) => Some(branch),
+        Head::Detached(commit) => commit.get(..7).map(str::to_string),
+    }
+}
+
+/// Commit a full reference name such as `refs/heads/main` points at, read
+/// from loose refs or `packed-refs`. Symbolic references are followed.
+pub fn resolve_ref(git_dir: &Path, name: &str) -> Option<String> {
+    let common = common_dir(git_dir);
+    let mut name = name.to_string();
+    // Bounded so that a cycle of symbolic refs cannot loop forever
+    for _ in 0..5 {
+        let loose = [git_dir, common.as_path()]
+            .iter()
+            .find_map(|dir| fs::read_to_string(dir.join(&name)).ok());
+        let value = match loose {
+            Some(value) => value.trim().to_string(),
+            None => return packed_ref(&common, &name),
+        };
+        match value.strip_prefix("ref:") {
+            Some(target) => name = target.trim().to_string(),
+            None => return Some(value),
+        }
+    }
+    None
+}
+
+fn packed_ref(common_dir: &Path, name: &str) -> Option<String> {
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .find_map(|line| {
+            let (commit, reference) = line.split_once(' ')?;
+            (reference.trim() == name).then(|| commit.to_string())
+        })
+}
+
+/// A single `key = value` entry of a git config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfigEntry {
+    section: String,
+    subsection: Option<String>,
+    key: String,
+    value: String,
+}
+
+/// The entries of a repository's `config` file. Includes are not followed.
+#[derive(Debug, Clone, Default)]
+pub struct GitConfig {
+    entries: Vec<ConfigEntry>,
+}
+
+impl GitConfig {
+    /// Reads the config of a git directory, empty if it cannot be read
+    pub fn load(git_dir: &Path) -> Self {
+        fs::read_to_string(common_dir(git_dir).join("config"))
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+        let mut subsection = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header.split(']').next().unwrap_or_default();
+                match header.split_once([' ', '.']) {
+                    Some((name, sub)) => {
+                        section = name.to_lowercase();
+                        subsection = Some(sub.trim().trim_matches('"').to_string());
+                    }
+                    None => {
+                        section = header.trim().to_lowercase();
+                        subsection = None;
+                    }
+                }
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), config_value(value)),
+                // A key without a value is a boolean set to true
+                None => (line, "true".to_string()),
+            };
+            entries.push(ConfigEntry {
+                section: section.clone(),
+                subsection: subsection.clone(),
+                key: key.to_lowercase(),
+                value,
+            });
+        }
+        Self { entries }
+    }
+
+    /// Last value of `key` in `[section "subsection"]`
+    pub fn get(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.section == section
+                    && entry.subsection.as_deref() == subsection
+                    && entry.key == key
+            })
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Names of the subsections of `section`, such as the configured remotes,
+    /// in the order they first appear
+    pub fn subsections(&self, section: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for entry in &self.entries {
+            if entry.section != section {
+                continue;
+            }
+            if let Some(name) = entry.subsection.as_deref() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Value of a config entry with inline comments and quotes removed
+fn config_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => value.push(other),
+                None => {}
+            },
+            c => value.push(c),
+        }
+    }
+    value.trim_end().to_string()
+}
+
+/// A file tracked in the index, with the stat data git recorded for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Path relative to the working tree, `/`-separated
+    pub path: String,
+    /// Modification time in seconds, truncated to 32 bits
+    pub mtime: u32,
+    /// Nanosecond part of the modification time, 0 if git did not record it
+    pub mtime_nsec: u32,
+    pub size: u32,
+    pub mode: u32,
+    /// Merge stage, non-zero for unresolved conflicts
+    pub stage: u8,
+    /// Marked skip-worktree by a sparse checkout, so absent on purpose
+    pub skip_worktree: bool,
+}
+
+/// Mode of a submodule in the index
+pub const GITLINK_MODE: u32 = 0o160000;
+
+/// Reads the entries of a repository's index, or `None` if it is missing or
+/// in a format that is not understood. Versions 2 to 4 are supported.
+pub fn read_index(git_dir: &Path) -> Option<Vec<IndexEntry>> {
+    let data = fs::read(git_dir.join("index")).ok()?;
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = be32(&data, 4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let count = be32(&data, 8)? as usize;
+    let hash_len = match GitConfig::load(git_dir).get("extensions", None, "objectformat") {
+        Some("sha256") => 32,
+        _ => 20,
+    };
+
+    let mut entries = Vec::with_capacity(count);
+    let mut offset = 12;
+    let mut previous = Vec::new();
+    for _ in 0..count {
+        let start = offset;
+        let mtime = be32(&data, start + 8)?;
+        let mtime_nsec = be32(&data, start + 12)?;
+        let mode = be32(&data, start + 24)?;
+        let size = be32(&data, start + 36)?;
+        offset = start + 40 + hash_len;
+        let flags = be16(&data, offset)?;
+        offset += 2;
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            skip_worktree = be16(&data, offset)? & 0x4000 != 0;
+            offset += 2;
+        }
+
+        let path = if version == 4 {
+            // The path is stored as the number of bytes to drop from the
+            // previous path followed by the remaining suffix
+            let (strip, used) = varint(&data, offset)?;
+            offset += used;
+            let end = offset + data.get(offset..)?.iter().position(|&b| b == 0)?;
+            let keep = previous.len().checked_sub(strip)?;
+            let mut path = previous[..keep].to_vec();
+            path.extend_from_slice(&data[offset..end]);
+            offset = end + 1;
+            path
+        } else {
+            let end = offset + data.get(offset..)?.iter().position(|&b| b == 0)?;
+            let path = data[offset..end].to_vec();
+            // Entries are padded with NULs to a multiple of eight bytes
+            offset = start + ((end - start) / 8 + 1) * 8;
+            path
+        };
+
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&path).to_string(),
+            mtime,
+            mtime_nsec,
+            size,
+            mode,
+            stage: ((flags >> 12) & 0x3) as u8,
+            skip_worktree,
+        });
+        previous = path;
+    }
+    Some(entries)
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Git's offset varint, returning the value and the number of bytes read
+fn varint(data: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let mut used = 0;
+    let mut byte = *data.get(offset)?;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        used += 1;
+        byte = *data.get(offset + used)?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Some((value, used + 1))
+}
+
+/// An entry of the `HEAD` reflog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub commit: String,
+    /// Seconds since epoch
+    pub time: u64,
+    /// What moved HEAD, such as `commit: Fix parser`
+    pub message: String,
+}
+
+/// Entries of the `HEAD` reflog, most recent first
+pub fn read_reflog(git_dir: &Path) -> Vec<ReflogEntry> {
+    let Ok(log) = fs::read_to_string(git_dir.join("logs").join("HEAD")) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<ReflogEntry> = log
+        .lines()
+        .filter_map(|line| {
+            let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+            let mut fields = header.split(' ');
+            let _old = fields.next()?;
+            let commit = fields.next()?.to_string();
+            // The identity may contain spaces; the time follows the email
+            let (_, after_email) = header.rsplit_once('>')?;
+            let time = after_email.split_whitespace().next()?.parse().ok()?;
+            Some(ReflogEntry {
+                commit,
+                time,
+                message: message.to_string(),
+            })
+        })
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// URL of the `origin` remote of a repository, if it has one
+pub fn origin_url(repo_path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["config", "--get", "remote.origin.url"])
+        .output()
+        .ok()?;
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !url.is_empty()).then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_head_branch() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        assert_eq!(head_branch(&repo).as_deref(), Some("feature/x"));
+
+        fs::write(
+            repo.join(".git/HEAD"),
+            "0123456789abcdef0123456789abcdef01234567\n",
+        )
+        .unwrap();
+        assert_eq!(head_branch(&repo).as_deref(), Some("0123456"));
+
+        // A linked worktree has its own HEAD in the directory its .git file points at
+        let worktree = dir.path().join("wt");
+        let worktree_git = repo.join(".git/worktrees/wt");
+        fs::create_dir_all(&worktree_git).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree_git.join("HEAD"), "ref: refs/heads/other\n").unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git/worktrees/wt\n").unwrap();
+        assert_eq!(
+            git_dir(&worktree),
+            Some(worktree.join("../repo/.git/worktrees/wt"))
+        );
+        assert_eq!(head_branch(&worktree).as_deref(), Some("other"));
+
+        assert_eq!(head_branch(dir.path()), None);
+    }
+
+    #[test]
+    fn test_resolve_ref() {
+        let dir = tempdir().unwrap();
+        let git_dir = dir.path().join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("refs/heads/loose"), "1111111\n").unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            "# pack-refs with: peeled fully-peeled sorted\n2222222 refs/heads/packed\n^3333333\n",
+        )
+        .unwrap();
+        fs::write(git_dir.join("refs/heads/alias"), "ref: refs/heads/packed\n").unwrap();
+
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/loose").as_deref(),
+            Some("1111111")
+        );
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/packed").as_deref(),
+            Some("2222222")
+        );
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/alias").as_deref(),
+            Some("2222222")
+        );
+        assert_eq!(resolve_ref(&git_dir, "refs/heads/missing"), None);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = GitConfig::parse(
+            r#"
+[core]
+    bare = false
+    logallrefupdates
+[remote "origin"]
+    url = https://github.com/org/repo.git ; the main remote
+    fetch = +refs/heads/*:refs/remotes/origin/*
+[Remote "upstream"]
+    URL = "git@github.com:up/repo.git"
+[branch "main"]
+    remote = origin
+    merge = refs/heads/main
+"#,
+        );
+        assert_eq!(config.get("core", None, "bare"), Some("false"));
+        assert_eq!(config.get("core", None, "logallrefupdates"), Some("true"));
+        assert_eq!(
+            config.get("remote", Some("origin"), "url"),
+            Some("https://github.com/org/repo.git")
+        );
+        assert_eq!(
+            config.get("remote", Some("upstream"), "url"),
+            Some("git@github.com:up/repo.git")
+        );
+        assert_eq!(config.subsections("remote"), vec!["origin", "upstream"]);
+        assert_eq!(
+            config.get("branch", Some("main"), "merge"),
+            Some("refs/heads/main")
+        );
+    }
+
+    #[test]
+    fn test_read_index_and_reflog() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init", "-q", "-b", "main"]);
+        fs::create_dir_all(repo.join("src/nested")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("src/lib.rs"), "fn main() {}").unwrap();
+        fs::write(repo.join("src/nested/mod.rs"), "").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-q", "-m", "Initial commit"]);
+
+        let git_dir = git_dir(repo).unwrap();
+        let entries = read_index(&git_dir).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["README.md", "src/lib.rs", "src/nested/mod.rs"]);
+        assert_eq!(entries[0].size, 5);
+
+        // Version 4 compresses paths against the previous entry
+        git(repo, &["update-index", "--index-version", "4"]);
+        let entries_v4 = read_index(&git_dir).unwrap();
+        assert_eq!(entries_v4, entries);
+
+        let reflog = read_reflog(&git_dir);
+        assert_eq!(reflog.len(), 1);
+        assert_eq!(reflog[0].message, "commit (initial): Initial commit");
+        assert_eq!(
+            resolve_ref(&git_dir, "refs/heads/main"),
+            Some(reflog[0].commit.clone())
+        );
+        assert_eq!(read_head(&git_dir), Some(Head::Branch("main".to_string())));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::Config;
+use crate::error::{RkitError, RkitResult};
+use crate::repo_url::ParsedRepoUrl;
+
+/// Layout used when none is configured
+pub const DEFAULT_LAYOUT: &str = "{domain}/{org}/{repo}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    Domain,
+    Org,
+    Repo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Var(Var),
+}
+
+/// Repository metadata recovered from a path by [`Layouts::extract`]. Fields
+/// are `None` when the matching layout does not contain them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutMatch {
+    pub domain: Option<String>,
+    pub org: Option<String>,
+    pub repo: Option<String>,
+}
+
+/// A template describing where a repository lives under the project root,
+/// such as `{domain}/{org}/{repo}` or `work/{org}-{repo}`.
+///
+/// A path segment consisting only of `{org}` spans every level of a nested
+/// group; when `{org}` is combined with other text in a segment, nested
+/// groups are joined with `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    template: String,
+    segments: Vec<Vec<Token>>,
+}
+
+impl Layout {
+    pub fn parse(template: &str) -> RkitResult<Self> {
+        let invalid = |reason: &str| {
+            RkitError::ConfigError(format!("Invalid layout '{}': {}", template, reason))
+        };
+
+        let mut segments = Vec::new();
+        for segment in template.split('/').filter(|s| !s.is_empty()) {
+            if segment == "." || segment == ".." {
+                return Err(invalid("'.' and '..' segments are not allowed"));
+            }
+
+            let mut tokens = Vec::new();
+            let mut rest = segment;
+            while !rest.is_empty() {
+                if let Some(after_brace) = rest.strip_prefix('{') {
+                    let end = after_brace
+                        .find('}')
+                        .ok_or_else(|| invalid("unclosed '{'"))?;
+                    let var = match &after_brace[..end] {
+                        "domain" => Var::Domain,
+                        "org" => Var::Org,
+                        "repo" => Var::Repo,
+                        other => return Err(invalid(&format!("unknown variable {{{}}}", other))),
+                    };
+                    if matches!(tokens.last(), Some(Token::Var(_))) {
+                        return Err(invalid("variables must be separated by text"));
+                    }
+                    tokens.push(Token::Var(var));
+                    rest = &after_brace[end + 1..];
+                } else {
+                    let end = rest.find('{').unwrap_or(rest.len());
+                    tokens.push(Token::Literal(rest[..end].to_string()));
+                    rest = &rest[end..];
+                }
+            }
+            segments.push(tokens);
+        }
+
+        let has_repo = segments
+            .iter()
+            .flatten()
+            .any(|t| *t == Token::Var(Var::Repo));
+        if !has_repo {
+            return Err(invalid("must contain {repo}"));
+        }
+
+        let spanning_orgs = segments
+            .iter()
+            .filter(|s| s.as_slice() == [Token::Var(Var::Org)])
+            .count();
+        if spanning_orgs > 1 {
+            return Err(invalid("{org} may only fill a whole path segment once"));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    fn has_var(&self, var: Var) -> bool {
+        self.segments
+            .iter()
+            .flatten()
+            .any(|t| *t == Token::Var(var))
+    }
+
+    /// Path of a repository relative to the project root
+    pub fn render(&self, parsed: &ParsedRepoUrl) -> PathBuf {
+        let mut path = PathBuf::new();
+        for segment in &self.segments {
+            if segment.as_slice() == [Token::Var(Var::Org)] {
+                path.extend(parsed.namespace());
+                continue;
+            }
+
+            let rendered: String = segment
+                .iter()
+                .map(|token| match token {
+                    Token::Literal(text) => text.clone(),
+                    Token::Var(Var::Domain) => parsed.domain.clone(),
+                    Token::Var(Var::Org) => parsed.org.replace('/', "-"),
+                    Token::Var(Var::Repo) => parsed.repo.clone(),
+                })
+                .collect();
+            path.push(rendered);
+        }
+        path
+    }
+
+    /// Recovers repository metadata from a path relative to the project root,
+    /// or `None` if the path does not follow this layout
+    pub fn extract(&self, relative_path: &Path) -> Option<LayoutMatch> {
+        let parts: Vec<String> = relative_path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+
+        let spanning = self
+            .segments
+            .iter()
+            .position(|s| s.as_slice() == [Token::Var(Var::Org)]);
+        let mut result = LayoutMatch::default();
+
+        match spanning {
+            Some(index) => {
+                let fixed = self.segments.len() - 1;
+                if parts.len() <= fixed {
+                    return None;
+                }
+                let org_len = parts.len() - fixed;
+                for (segment, part) in self.segments[..index].iter().zip(&parts[..index]) {
+                    match_segment(segment, part, &mut result)?;
+                }
+                result.org = Some(parts[index..index + org_len].join("/"));
+                for (segment, part) in self.segments[index + 1..]
+                    .iter()
+                    .zip(&parts[index + org_len..])
+                {
+                    match_segment(segment, part, &mut result)?;
+                }
+            }
+            None => {
+                if parts.len() != self.segments.len() {
+                    return None;
+                }
+                for (segment, part) in self.segments.iter().zip(&parts) {
+                    match_segment(segment, part, &mut result)?;
+                }
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Ordering key preferring layouts that are less likely to match by
+    /// accident: more literal text first, then more path segments
+    fn specificity(&self) -> (usize, usize) {
+        let literal_len = self
+            .segments
+            .iter()
+            .flatten()
+            .map(|t| match t {
+                Token::Literal(text) => text.len(),
+                Token::Var(_) => 0,
+            })
+            .sum();
+        (literal_len, self.segments.len())
+    }
+}
+
+/// Matches a single path segment against a template segment, storing
+/// captured variables. Earlier variables capture as little text as possible.
+fn match_segment(tokens: &[Token], part: &str, result: &mut LayoutMatch) -> Option<()> {
+    fn capture(tokens: &[Token], part: &str, captured: &mut Vec<(Var, String)>) -> bool {
+        match tokens.split_first() {
+            None => part.is_empty(),
+            Some((Token::Literal(text), rest)) => part
+                .strip_prefix(text.as_str())
+                .is_some_and(|remaining| capture(rest, remaining, captured)),
+            Some((Token::Var(var), rest)) => {
+                let ends: Vec<usize> = part.char_indices().map(|(i, c)| i + c.len_utf8()).collect();
+                for end in ends {
+                    captured.push((*var, part[..end].to_string()));
+                    if capture(rest, &part[end..], captured) {
+                        return true;
+                    }
+                    captured.pop();
+                }
+                false
+            }
+        }
+    }
+
+    let mut captured = Vec::new();
+    if !capture(tokens, part, &mut captured) {
+        return None;
+    }
+
+    for (var, value) in captured {
+        let slot = match var {
+            Var::Domain => &mut result.domain,
+            Var::Org => &mut result.org,
+            Var::Repo => &mut result.repo,
+        };
+        // The same variable used twice must capture the same text
+        if slot.as_ref().is_some_and(|existing| *existing != value) {
+            return None;
+        }
+        *slot = Some(value);
+    }
+    Some(())
+}
+
+/// The default layout together with per-domain overrides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layouts {
+    default: Layout,
+    domains: HashMap<String, Layout>,
+}
+
+impl Default for Layouts {
+    fn default() -> Self {
+        Self {
+            default: Layout::parse(DEFAULT_LAYOUT).expect("default layout is valid"),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+impl Layouts {
+    pub fn new(default: Layout) -> Self {
+        Self {
+            default,
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Uses `layout` for repositories from `domain`
+    pub fn with_domain(mut self, domain: &str, layout: Layout) -> Self {
+        self.domains.insert(domain.to_lowercase(), layout);
+        self
+    }
+
+    pub fn from_config(config: &Config) -> RkitResult<Self> {
+        let default = match &config.layout {
+            Some(template) => Layout::parse(template)?,
+            None => Layout::parse(DEFAULT_LAYOUT)?,
+        };
+
+        let mut layouts = Self::new(default);
+        for (domain, domain_config) in &config.domains {
+            if let Some(template) = &domain_config.layout {
+                layouts = layouts.with_domain(domain, Layout::parse(template)?);
+            }
+        }
+        Ok(layouts)
+    }
+
+    /// Layout used for repositories from `domain`
+    pub fn for_domain(&self, domain: &str) -> &Layout {
+        self.domains
+            .get(&domain.to_lowercase())
+            .unwrap_or(&self.default)
+    }
+
+    /// Path of a repository relative to the project root
+    pub fn render(&self, parsed: &ParsedRepoUrl) -> PathBuf {
+        self.for_domain(&parsed.domain).render(parsed)
+    }
+
+    /// Recovers repository metadata from a path relative to the project root
+    /// by matching it against every configured layout
+    pub fn extract(&self, relative_path: &Path) -> Option<LayoutMatch> {
+        let mut candidates: Vec<(Option<&str>, &Layout)> = self
+            .domains
+            .iter()
+            .map(|(domain, layout)| (Some(domain.as_str()), layout))
+            .collect();
+        candidates.push((None, &self.default));
+        candidates.sort_by(|a, b| {
+            b.1.specificity()
+                .cmp(&a.1.specificity())
+                .then_with(|| b.0.is_some().cmp(&a.0.is_some()))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        candidates.into_iter().find_map(|(domain, layout)| {
+            let mut found = layout.extract(relative_path)?;
+            match domain {
+                Some(domain) => match &found.domain {
+                    Some(captured) if !captured.eq_ignore_ascii_case(domain) => return None,
+                    Some(_) => {}
+                    None => found.domain = Some(domain.to_string()),
+                },
+                None => {
+                    // A domain with its own layout never uses the default one
+                    let overridden = found
+                        .domain
+                        .as_ref()
+                        .is_some_and(|d| self.domains.contains_key(&d.to_lowercase()));
+                    if overridden {
+                        return None;
+                    }
+                }
+            }
+            if !layout.has_var(Var::Org) {
+                found.org = None;
+            }
+            Some(found)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_url::parse_repo_url;
+
+    fn layout(template: &str) -> Layout {
+        Layout::parse(template).unwrap()
+    }
+
+    #[test]
+    fn test_default_layout_round_trip() {
+        let layouts = Layouts::default();
+        let parsed = parse_repo_url("https://gitlab.com/group/sub/project.git").unwrap();
+        let path = layouts.render(&parsed);
+        assert_eq!(
+            path,
+            Path::new("gitlab.com")
+                .join("group")
+                .join("sub")
+                .join("project")
+        );
+
+        let found = layouts.extract(&path).unwrap();
+        assert_eq!(found.domain.as_deref(), Some("gitlab.com"));
+        assert_eq!(found.org.as_deref(), Some("group/sub"));
+        assert_eq!(found.repo.as_deref(), Some("project"));
+    }
+
+    #[test]
+    fn test_default_layout_rejects_short_paths() {
+        assert!(Layouts::default().extract(Path::new("org/repo")).is_none());
+    }
+
+    #[test]
+    fn test_embedded_variables() {
+        let layout = layout("{org}-{repo}");
+        let parsed = parse_repo_url("https://github.com/imthor/rkit").unwrap();
+        assert_eq!(layout.render(&parsed), Path::new("imthor-rkit"));
+
+        let found = layout.extract(Path::new("imthor-rkit")).unwrap();
+        assert_eq!(found.org.as_deref(), Some("imthor"));
+        assert_eq!(found.repo.as_deref(), Some("rkit"));
+        assert_eq!(found.domain, None);
+    }
+
+    #[test]
+    fn test_per_domain_layout() {
+        let layouts = Layouts::default().with_domain("git.corp.example.com", layout("work/{repo}"));
+
+        let parsed = parse_repo_url("https://git.corp.example.com/team/service").unwrap();
+        assert_eq!(layouts.render(&parsed), Path::new("work").join("service"));
+
+        let found = layouts.extract(&Path::new("work").join("service")).unwrap();
+        assert_eq!(found.domain.as_deref(), Some("git.corp.example.com"));
+        assert_eq!(found.org, None);
+        assert_eq!(found.repo.as_deref(), Some("service"));
+
+        let found = layouts
+            .extract(&Path::new("github.com").join("org").join("repo"))
+            .unwrap();
+        assert_eq!(found.domain.as_deref(), Some("github.com"));
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        assert!(Layout::parse("{domain}/{org}").is_err());
+        assert!(Layout::parse("{domain}/{owner}/{repo}").is_err());
+        assert!(Layout::parse("{org}{repo}").is_err());
+        assert!(Layout::parse("../{repo}").is_err());
+        assert!(Layout::parse("{org}/{org}/{repo}").is_err());
+        assert!(Layout::parse("{repo").is_err());
+    }
+
+    #[test]
+    fn test_layouts_from_config() {
+        let yaml = r#"
+project_root: ~/projects
+rview:
+layout: "{org}/{repo}"
+domains:
+  github.example.com:
+    layout: "enterprise/{org}/{repo}"
+"#;
+        let config: Config = serde_yml::from_str(yaml).unwrap();
+        let layouts = Layouts::from_config(&config).unwrap();
+        assert_eq!(layouts.for_domain("github.com").template(), "{org}/{repo}");
+        assert_eq!(
+            layouts.for_domain("GitHub.example.com").template(),
+            "enterprise/{org}/{repo}"
+        );
+    }
+}
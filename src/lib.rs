@@ -2,6 +2,22 @@ pub mod cache;
 pub mod commands;
 pub mod config;
 pub mod error;
+pub mod filter;
+pub mod fuzzy;
+pub mod git;
+pub mod layout;
+pub mod manifest;
+pub mod process;
+pub mod repo_url;
+pub mod scanner;
+pub mod sections;
+pub mod template;
+pub mod usage;
+pub mod workers;
+pub mod workspace;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 use std::sync::LazyLock;
 
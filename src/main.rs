@@ -1,10 +1,29 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::env;
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use rkit::commands;
-use rkit::commands::ls::WalkerConfig;
+use rkit::commands::clone::{CloneOptions, ExistingStrategy};
+use rkit::commands::exec::ExecOptions;
+use rkit::commands::fetch::{UpdateMode, UpdateOptions};
+use rkit::commands::ls::{ListOptions, OutputFormat, SortKey, WalkerConfig};
+use rkit::commands::pick::PickOptions;
+use rkit::commands::recent::RecentOptions;
+use rkit::commands::status::{StatusFormat, StatusOptions};
+use rkit::commands::sync::SyncOptions;
 use rkit::config;
-use rkit::error::RkitResult;
+use rkit::config::{CloneFlags, RootConfig};
+use rkit::error::{RkitError, RkitResult};
+use rkit::filter::{parse_age, RepoFilter};
+use rkit::layout::Layouts;
+use rkit::manifest::Manifest;
+use rkit::repo_url;
+use rkit::scanner::{build_excludes, RepoKind};
+use rkit::template::TemplateVars;
+use rkit::workspace::{ProjectRoot, Workspace};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,41 +35,410 @@ struct Cli {
     verbose: u8,
 }
 
+/// Options shared by `fetch` and `pull`
+#[derive(Args)]
+struct UpdateArgs {
+    /// Show full paths instead of relative paths
+    #[arg(short, long)]
+    full: bool,
+    /// Seconds a single repository may take before it is aborted (0 for no limit)
+    #[arg(long, default_value_t = 120)]
+    timeout: u64,
+    #[command(flatten)]
+    walker: WalkerArgs,
+}
+
+impl UpdateArgs {
+    fn to_options(&self, mode: UpdateMode) -> UpdateOptions {
+        UpdateOptions {
+            mode,
+            timeout: (self.timeout > 0).then(|| Duration::from_secs(self.timeout)),
+            full: self.full,
+            refresh: self.walker.refresh,
+        }
+    }
+}
+
+/// Flags passed through to `git clone`
+#[derive(Args)]
+struct CloneArgs {
+    /// Create a shallow clone with this many commits
+    #[arg(long)]
+    depth: Option<u32>,
+    /// Check out this branch instead of the remote's HEAD
+    #[arg(short, long)]
+    branch: Option<String>,
+    /// Only fetch the history of a single branch
+    #[arg(long)]
+    single_branch: bool,
+    /// Partial clone filter, e.g. `blob:none`
+    #[arg(long)]
+    filter: Option<String>,
+    /// Initialize and clone submodules
+    #[arg(long)]
+    recurse_submodules: bool,
+    /// Create a bare repository
+    #[arg(long, conflicts_with = "mirror")]
+    bare: bool,
+    /// Create a bare mirror of the remote
+    #[arg(long)]
+    mirror: bool,
+}
+
+impl CloneArgs {
+    fn to_flags(&self) -> CloneFlags {
+        CloneFlags {
+            depth: self.depth,
+            branch: self.branch.clone(),
+            single_branch: self.single_branch,
+            filter: self.filter.clone(),
+            recurse_submodules: self.recurse_submodules,
+            bare: self.bare,
+            mirror: self.mirror,
+        }
+    }
+}
+
+/// Directory walker options shared by every command that scans the workspace
+#[derive(Args)]
+struct WalkerArgs {
+    /// Maximum depth to search for repositories [default: 10]
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Follow symbolic links [default: false]
+    #[arg(long)]
+    follow_links: bool,
+    /// Stay on the same filesystem [default: true]
+    #[arg(long)]
+    same_file_system: bool,
+    /// Number of threads to use for searching [default: number of CPU cores]
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Maximum number of repositories to find [default: no limit]
+    #[arg(long)]
+    max_repos: Option<usize>,
+    /// Don't skip repositories that are inside other repositories
+    #[arg(long)]
+    no_stop_at_git: bool,
+    /// Rescan the workspace instead of using the cached repository index
+    #[arg(long)]
+    refresh: bool,
+    /// Only include these kinds of repository [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    kind: Vec<RepoKind>,
+    /// Exclude these kinds of repository
+    #[arg(long, value_enum, value_delimiter = ',')]
+    exclude_kind: Vec<RepoKind>,
+    /// Only scan the project root with this name (repeatable) [default: all]
+    #[arg(long)]
+    root: Vec<String>,
+    /// Skip paths matching this gitignore-style glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// Ignore `.rkitignore` files and configured excludes, keeping --exclude globs
+    #[arg(long)]
+    no_ignore: bool,
+}
+
+impl WalkerArgs {
+    /// Walker settings for a root, preferring flags given on the command
+    /// line over the root's own settings
+    fn to_config(&self, root: &RootConfig) -> WalkerConfig {
+        WalkerConfig {
+            max_depth: self.max_depth.or(root.max_depth),
+            follow_links: self.follow_links || root.follow_links.unwrap_or(false),
+            same_file_system: self.same_file_system || root.same_file_system.unwrap_or(false),
+            threads: self.threads.unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            }),
+            max_repos: self.max_repos.or(root.max_repos),
+            stop_at_git: !self.no_stop_at_git && root.stop_at_git.unwrap_or(true),
+            kinds: RepoKind::ALL
+                .into_iter()
+                .filter(|kind| self.kind.is_empty() || self.kind.contains(kind))
+                .filter(|kind| !self.exclude_kind.contains(kind))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Repository selection options shared by commands that act on a subset of
+/// the workspace
+#[derive(Args)]
+struct FilterArgs {
+    /// Only include repositories under this domain (repeatable)
+    #[arg(long)]
+    domain: Vec<String>,
+    /// Only include repositories in this organization or group (repeatable)
+    #[arg(long)]
+    org: Vec<String>,
+    /// Only include repositories whose relative path matches this glob (repeatable)
+    #[arg(long)]
+    glob: Vec<String>,
+    /// Only include repositories containing this file or directory (repeatable)
+    #[arg(long, value_name = "PATH")]
+    has_file: Vec<PathBuf>,
+    /// Only include repositories used within this age, e.g. `12h`, `7d` or `2w`
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    modified_since: Option<Duration>,
+}
+
+impl FilterArgs {
+    fn to_filter(&self, config: &config::Config) -> RkitResult<RepoFilter> {
+        Ok(RepoFilter::new()
+            .domains(self.domain.clone())
+            .orgs(self.org.clone())
+            .globs(&self.glob)?
+            .has_files(self.has_file.clone())
+            .modified_within(self.modified_since)
+            .layouts(Layouts::from_config(config)?))
+    }
+}
+
+/// Reruns the current `rkit ls` with `--refresh` as a detached process, so an
+/// index served while stale is rebuilt without delaying the listing
+fn revalidate_in_background() {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    let Some(ls) = args.iter().position(|arg| arg == "ls") else {
+        return;
+    };
+    args.insert(ls + 1, "--refresh".into());
+
+    let spawned = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    match spawned {
+        Ok(child) => log::debug!(
+            "Revalidating the repository index in process {}",
+            child.id()
+        ),
+        Err(e) => log::warn!("Failed to revalidate the repository index: {}", e),
+    }
+}
+
+/// Builds the workspace from the configured roots. Without walker flags,
+/// roots are scanned with their own settings on top of the defaults.
+fn build_workspace(config: &config::Config, walker: Option<&WalkerArgs>) -> RkitResult<Workspace> {
+    let selected = walker.map(|w| w.root.as_slice()).unwrap_or_default();
+    // Threads are a workspace-wide setting, shared by the walkers of all roots
+    let threads = walker.and_then(|w| w.threads).or(config.threads);
+    let mut roots = Vec::new();
+    for (root, path) in config.project_roots()? {
+        if !selected.is_empty() && !selected.contains(&root.name) {
+            continue;
+        }
+        let mut walker_config = match walker {
+            Some(walker) => walker.to_config(&root),
+            None => {
+                let defaults = WalkerConfig::default();
+                WalkerConfig {
+                    max_depth: root.max_depth.or(defaults.max_depth),
+                    follow_links: root.follow_links.unwrap_or(defaults.follow_links),
+                    same_file_system: root.same_file_system.unwrap_or(defaults.same_file_system),
+                    max_repos: root.max_repos,
+                    stop_at_git: root.stop_at_git.unwrap_or(defaults.stop_at_git),
+                    ..defaults
+                }
+            }
+        };
+        // Explicit --exclude globs apply even with --no-ignore
+        let cli_excludes = walker.map(|w| w.exclude.as_slice()).unwrap_or_default();
+        if walker.is_some_and(|w| w.no_ignore) {
+            walker_config.ignore_files = false;
+            walker_config.excludes = cli_excludes.to_vec();
+        } else {
+            walker_config.excludes = config
+                .excludes
+                .iter()
+                .chain(&root.excludes)
+                .chain(cli_excludes)
+                .cloned()
+                .collect();
+        }
+        if let Some(threads) = threads {
+            walker_config.threads = threads;
+        }
+        build_excludes(&path, &walker_config.excludes)?;
+        roots.push(ProjectRoot::new(&root.name, &path, walker_config));
+    }
+
+    if let Some(unknown) = selected
+        .iter()
+        .find(|name| !roots.iter().any(|r| &r.name == *name))
+    {
+        return Err(RkitError::ConfigError(format!(
+            "Unknown root '{}'",
+            unknown
+        )));
+    }
+    let workspace = Workspace::new(roots)?;
+    Ok(match threads {
+        Some(threads) => workspace.with_threads(threads),
+        None => workspace,
+    })
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Smart Git clone wrapper
     Clone {
-        /// Git repository URL to clone
+        /// Git repository URL, `org/repo` shorthand or `alias:org/repo`
         url: String,
+        #[command(flatten)]
+        args: CloneArgs,
+        /// Replace a target directory that holds something else
+        #[arg(long, conflicts_with = "suffix")]
+        force: bool,
+        /// Clone next to a target directory that holds something else, as `<repo>-<n>`
+        #[arg(long)]
+        suffix: bool,
+        /// Fetch the repository if it is already cloned
+        #[arg(long)]
+        fetch: bool,
+        /// Name of the project root to clone into
+        #[arg(long)]
+        root: Option<String>,
     },
     /// List Git repositories in workspace
     Ls {
+        /// Only list repositories whose relative path matches this glob
+        pattern: Option<String>,
+        /// Treat the pattern as a regular expression searched for in the relative path
+        #[arg(short = 'E', long, requires = "pattern")]
+        regex: bool,
         /// Show full paths instead of relative paths
         #[arg(short, long)]
         full: bool,
-        /// Maximum depth to search for repositories [default: 10]
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        walker: WalkerArgs,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+        format: OutputFormat,
+        /// Separate paths with NUL bytes instead of newlines (same as --format null)
+        #[arg(short = '0', long = "null", conflicts_with = "format")]
+        null: bool,
+        /// Print repositories in this order once the scan completes instead of as they are found
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+    },
+    /// Pick a repository with a fuzzy finder and print its path
+    Pick {
+        /// Query the finder starts with
+        query: Option<String>,
+        /// List full paths instead of relative paths
+        #[arg(short, long)]
+        full: bool,
+        /// Don't show the `rview` sections of the highlighted repository
         #[arg(long)]
-        max_depth: Option<usize>,
-        /// Follow symbolic links [default: false]
+        no_preview: bool,
+        #[command(flatten)]
+        walker: WalkerArgs,
+    },
+    /// List visited repositories, most frequently and recently used first
+    Recent {
+        /// Print at most this many repositories
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Show full paths instead of relative paths
+        #[arg(short, long)]
+        full: bool,
+        /// Print the frecency score before each path
+        #[arg(long)]
+        scores: bool,
+    },
+    /// Show branch and working tree state of every repository
+    Status {
+        /// Show full paths instead of relative paths
+        #[arg(short, long)]
+        full: bool,
+        #[command(flatten)]
+        walker: WalkerArgs,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = StatusFormat::Table)]
+        format: StatusFormat,
+        /// Only show repositories with uncommitted changes or untracked files
         #[arg(long)]
-        follow_links: bool,
-        /// Stay on the same filesystem [default: true]
+        dirty: bool,
+        /// Only show repositories with unpushed commits
+        #[arg(long)]
+        ahead: bool,
+        /// Only show repositories behind their upstream
+        #[arg(long)]
+        behind: bool,
+    },
+    /// Fetch every repository in parallel
+    Fetch {
+        #[command(flatten)]
+        args: UpdateArgs,
+    },
+    /// Fast-forward pull every repository in parallel
+    Pull {
+        #[command(flatten)]
+        args: UpdateArgs,
+    },
+    /// Run a command in every repository
+    Exec {
+        /// Run in one repository at a time instead of in parallel
         #[arg(long)]
-        same_file_system: bool,
-        /// Number of threads to use for searching [default: number of CPU cores]
+        serial: bool,
+        /// Print each repository's output as one block instead of prefixed lines
         #[arg(long)]
-        threads: Option<usize>,
-        /// Maximum number of repositories to find [default: no limit]
+        group: bool,
+        /// Label output with full paths instead of relative paths
+        #[arg(short, long)]
+        full: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        walker: WalkerArgs,
+        /// Command and arguments to run
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Clone missing repositories listed in a manifest
+    Sync {
+        /// Path to the manifest file
+        manifest: PathBuf,
+        /// Fetch repositories that are already present
         #[arg(long)]
-        max_repos: Option<usize>,
-        /// Don't skip repositories that are inside other repositories
+        fetch: bool,
+        /// Only sync entries with this tag (repeatable)
         #[arg(long)]
-        no_stop_at_git: bool,
+        tag: Vec<String>,
+        /// Seconds a single fetch may take before it is aborted (0 for no limit)
+        #[arg(long, default_value_t = 120)]
+        timeout: u64,
+        /// Show full paths instead of relative paths
+        #[arg(short, long)]
+        full: bool,
+        #[command(flatten)]
+        walker: WalkerArgs,
+    },
+    /// Write a manifest describing the repositories in the workspace
+    Export {
+        /// Write the manifest to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        walker: WalkerArgs,
     },
     /// View repository information
     View {
         /// Path to repository
         path: PathBuf,
+        /// Print the sections as a JSON object keyed by label
+        #[arg(long)]
+        json: bool,
     },
 }
 
@@ -72,45 +460,189 @@ fn main() -> RkitResult<()> {
         .init();
 
     // Get project root from config or use default
-    let project_root = config::Config::load_or_create()?.expand_project_root()?;
+    let config = config::Config::load_or_create()?;
 
     match args.command {
-        Commands::Clone { url } => {
+        Commands::Clone {
+            url,
+            args,
+            force,
+            suffix,
+            fetch,
+            root,
+        } => {
+            let url = repo_url::expand_repo_url(&url, &config)?;
             log::info!("Cloning repository: {}", url);
-            commands::clone::clone(&url, &project_root)
+            let options = CloneOptions {
+                flags: args.to_flags(),
+                existing: if force {
+                    ExistingStrategy::Force
+                } else if suffix {
+                    ExistingStrategy::Suffix
+                } else {
+                    ExistingStrategy::Fail
+                },
+                fetch_existing: fetch,
+                root,
+                ..CloneOptions::from_config(&config)?
+            };
+            commands::clone::clone(&url, &build_workspace(&config, None)?, &options)
         }
         Commands::Ls {
+            pattern,
+            regex,
             full,
-            max_depth,
-            follow_links,
-            same_file_system,
-            threads,
-            max_repos,
-            no_stop_at_git,
+            filter,
+            walker,
+            format,
+            null,
+            sort,
         } => {
-            let config = WalkerConfig {
-                max_depth,
-                follow_links,
-                same_file_system,
-                threads: threads.unwrap_or_else(|| {
-                    std::thread::available_parallelism()
-                        .map(|n| n.get())
-                        .unwrap_or(1)
-                }),
-                max_repos,
-                stop_at_git: !no_stop_at_git,
+            let mut repo_filter = filter.to_filter(&config)?;
+            if let Some(pattern) = pattern {
+                repo_filter = repo_filter.pattern(&pattern, regex)?;
+            }
+            let options = ListOptions {
+                full,
+                refresh: walker.refresh,
+                format: if null { OutputFormat::Null } else { format },
+                layouts: Layouts::from_config(&config)?,
+                filter: repo_filter,
+                sort,
             };
-            commands::ls::list_repos(&project_root, full, Some(config))
+            let workspace = build_workspace(&config, Some(&walker))?;
+            commands::ls::list_repos(&workspace, &options)?;
+            if !options.refresh && commands::ls::needs_revalidation(&workspace) {
+                revalidate_in_background();
+            }
+            Ok(())
         }
-        Commands::View { path } => {
+        Commands::Pick {
+            query,
+            full,
+            no_preview,
+            walker,
+        } => {
+            let options = PickOptions {
+                query: query.unwrap_or_default(),
+                full,
+                preview: !no_preview,
+                refresh: walker.refresh,
+                layouts: Layouts::from_config(&config)?,
+            };
+            let workspace = build_workspace(&config, Some(&walker))?;
+            let picked = commands::pick::pick_repo(&workspace, config.rview.as_deref(), &options)?;
+            if picked.is_none() {
+                // Like fzf, signal a cancelled selection with 130
+                std::process::exit(130);
+            }
+            Ok(())
+        }
+        Commands::Recent {
+            limit,
+            full,
+            scores,
+        } => {
+            let options = RecentOptions {
+                limit,
+                full,
+                scores,
+            };
+            commands::recent::recent_repos(&build_workspace(&config, None)?, &options)
+        }
+        Commands::Status {
+            full,
+            walker,
+            format,
+            dirty,
+            ahead,
+            behind,
+        } => {
+            let options = StatusOptions {
+                full,
+                refresh: walker.refresh,
+                format,
+                dirty,
+                ahead,
+                behind,
+            };
+            commands::status::status_repos(&build_workspace(&config, Some(&walker))?, &options)
+        }
+        Commands::Fetch { args } => commands::fetch::update_workspace(
+            &build_workspace(&config, Some(&args.walker))?,
+            &args.to_options(UpdateMode::Fetch),
+        ),
+        Commands::Pull { args } => commands::fetch::update_workspace(
+            &build_workspace(&config, Some(&args.walker))?,
+            &args.to_options(UpdateMode::Pull),
+        ),
+        Commands::Exec {
+            serial,
+            group,
+            full,
+            filter,
+            walker,
+            command,
+        } => {
+            let options = ExecOptions {
+                command,
+                serial,
+                group,
+                full,
+                refresh: walker.refresh,
+                filter: filter.to_filter(&config)?,
+            };
+            commands::exec::exec_repos(&build_workspace(&config, Some(&walker))?, &options)
+        }
+        Commands::Sync {
+            manifest,
+            fetch,
+            tag,
+            timeout,
+            full,
+            walker,
+        } => {
+            let mut manifest = Manifest::load(&manifest)?;
+            manifest.expand_urls(&config)?;
+            let options = SyncOptions {
+                clone: CloneOptions::from_config(&config)?,
+                fetch,
+                timeout: (timeout > 0).then(|| Duration::from_secs(timeout)),
+                tags: tag,
+                full,
+                refresh: walker.refresh,
+            };
+            commands::sync::sync_workspace(
+                &build_workspace(&config, Some(&walker))?,
+                &manifest,
+                &options,
+            )
+        }
+        Commands::Export { output, walker } => {
+            let manifest = commands::sync::export_manifest(
+                &build_workspace(&config, Some(&walker))?,
+                &CloneOptions::from_config(&config)?,
+                walker.refresh,
+            );
+            match output {
+                Some(path) => manifest.save(&path),
+                None => {
+                    print!("{}", manifest.to_yaml()?);
+                    Ok(())
+                }
+            }
+        }
+        Commands::View { path, json } => {
             log::info!("Viewing repository: {}", path.display());
-            let repo_path = if path.is_absolute() {
-                path
+            let workspace = build_workspace(&config, None)?;
+            let repo_path = workspace.resolve(&path);
+            let vars =
+                TemplateVars::for_repo(&workspace, &Layouts::from_config(&config)?, &repo_path);
+            if json {
+                commands::view::print_sections_json(&vars, config.rview.as_deref())
             } else {
-                project_root.join(path)
-            };
-            let config = config::Config::load_or_create()?;
-            commands::view::view_repo(&repo_path, config.rview.as_deref())
+                commands::view::view_repo(&vars, config.rview.as_deref())
+            }
         }
     }
 }
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::{RkitError, RkitResult};
+use crate::repo_url::expand_repo_url;
+
+/// A declarative list of the repositories that make up a workspace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub repos: Vec<ManifestRepo>,
+}
+
+/// A single repository in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRepo {
+    /// Clone URL, or a shorthand accepted by `rkit clone`
+    pub url: String,
+    /// Branch to check out when cloning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Layout template overriding the configured one for this repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Name of the root to clone into, overriding the configured rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> RkitResult<Self> {
+        let content = fs::read_to_string(path).map_err(|e| RkitError::FileReadError {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        Ok(serde_yml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> RkitResult<()> {
+        fs::write(path, self.to_yaml()?).map_err(|e| RkitError::FileWriteError {
+            path: path.to_path_buf(),
+            source: e,
+        })
+    }
+
+    pub fn to_yaml(&self) -> RkitResult<String> {
+        Ok(serde_yml::to_string(self)?)
+    }
+
+    /// Expands clone shorthands and host aliases in every URL
+    pub fn expand_urls(&mut self, config: &Config) -> RkitResult<()> {
+        for repo in &mut self.repos {
+            repo.url = expand_repo_url(&repo.url, config)?;
+        }
+        Ok(())
+    }
+}
+
+impl ManifestRepo {
+    /// Whether the entry has at least one of `tags`, or `tags` is empty
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let yaml = r#"
+repos:
+  - url: https://github.com/imthor/rkit
+    branch: main
+    tags: [cli]
+  - url: git@gitlab.com:group/sub/project.git
+    layout: "work/{repo}"
+"#;
+        let manifest: Manifest = serde_yml::from_str(yaml).unwrap();
+        assert_eq!(manifest.repos.len(), 2);
+        assert_eq!(manifest.repos[0].branch.as_deref(), Some("main"));
+        assert!(manifest.repos[0].has_any_tag(&["cli".to_string()]));
+        assert!(!manifest.repos[1].has_any_tag(&["cli".to_string()]));
+
+        let reparsed: Manifest = serde_yml::from_str(&manifest.to_yaml().unwrap()).unwrap();
+        assert_eq!(reparsed, manifest);
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often a running child is polled while waiting for it to exit
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Captured result of a command run with [`output_with_timeout`]
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    /// Exit status, or `None` if the command was killed after timing out
+    pub status: Option<ExitStatus>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Whether the command was killed for exceeding its timeout
+    pub timed_out: bool,
+    /// Time from spawning the command until it exited or was killed
+    pub duration: Duration,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.status.map(|s| s.success()).unwrap_or(false)
+    }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(
+    source: Option<R>,
+    buffer: Arc<Mutex<Vec<u8>>>,
+) -> Option<JoinHandle<()>> {
+    let mut source = source?;
+    Some(std::thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        loop {
+            match source.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Ok(mut buffer) = buffer.lock() {
+                        buffer.extend_from_slice(&chunk[..n]);
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn take_buffer(buffer: &Arc<Mutex<Vec<u8>>>) -> Vec<u8> {
+    buffer
+        .lock()
+        .map(|mut b| std::mem::take(&mut *b))
+        .unwrap_or_default()
+}
+
+/// Runs a command to completion capturing stdout and stderr, killing it if it
+/// runs longer than `timeout`
+pub fn output_with_timeout(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> io::Result<CommandOutput> {
+    let start = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = Arc::new(Mutex::new(Vec::new()));
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let readers = [
+        spawn_reader(child.stdout.take(), Arc::clone(&stdout)),
+        spawn_reader(child.stderr.take(), Arc::clone(&stderr)),
+    ];
+
+    let deadline = timeout.map(|t| start + t);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            log::debug!("Killing command after {:?}: {:?}", timeout, command);
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    // Grandchildren of a killed command (e.g. ssh under git) can keep the
+    // pipes open, so only wait for the readers when the command exited
+    if status.is_some() {
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+    }
+
+    Ok(CommandOutput {
+        status,
+        stdout: take_buffer(&stdout),
+        stderr: take_buffer(&stderr),
+        timed_out: status.is_none(),
+        duration: start.elapsed(),
+    })
+}
+
+/// Copies lines from `source` to stdout or stderr, each prefixed with `prefix`
+fn spawn_prefixer<R: Read + Send + 'static>(
+    source: Option<R>,
+    prefix: String,
+    to_stderr: bool,
+) -> Option<JoinHandle<()>> {
+    let source = source?;
+    Some(std::thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim_end_matches(['\n', '\r']);
+                    if to_stderr {
+                        eprintln!("{}{}", prefix, text);
+                    } else {
+                        println!("{}{}", prefix, text);
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Runs a command to completion, streaming its stdout and stderr line by line
+/// with every line prefixed by `prefix`
+pub fn run_prefixed(command: &mut Command, prefix: &str) -> io::Result<ExitStatus> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let readers = [
+        spawn_prefixer(child.stdout.take(), prefix.to_string(), false),
+        spawn_prefixer(child.stderr.take(), prefix.to_string(), true),
+    ];
+    let status = child.wait()?;
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    Ok(status)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_with_timeout_captures_output() {
+        let output = output_with_timeout(
+            Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+            None,
+        )
+        .unwrap();
+        assert!(output.success());
+        assert!(!output.timed_out);
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_output_with_timeout_kills_slow_command() {
+        let output = output_with_timeout(
+            Command::new("sleep").arg("5"),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+        assert!(output.timed_out);
+        assert!(!output.success());
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_run_prefixed_status() {
+        let status =
+            run_prefixed(Command::new("sh").args(["-c", "echo hi; exit 2"]), "[x] ").unwrap();
+        assert_eq!(status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_output_with_timeout_failure_status() {
+        let output = output_with_timeout(Command::new("sh").args(["-c", "exit 3"]), None).unwrap();
+        assert_eq!(output.status.and_then(|s| s.code()), Some(3));
+    }
+}
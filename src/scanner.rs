@@ -0,0 +1,659 @@
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{WalkBuilder, WalkState};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{RkitError, RkitResult};
+use crate::git::read_gitlink;
+
+/// Name of the gitignore-style files that exclude paths from discovery
+pub const IGNORE_FILENAME: &str = ".rkitignore";
+
+/// Statistics collected while scanning a project root
+#[derive(Debug, Clone, Default)]
+pub struct PerformanceMetrics {
+    /// Wall-clock time spent walking the tree
+    pub total_duration: Duration,
+    /// Number of repositories found
+    pub repo_count: usize,
+    /// Number of directory entries visited
+    pub scanned_dirs: usize,
+    /// Whether the scan stopped early because `max_repos` was reached
+    pub limit_reached: bool,
+    /// `.rkitignore` files that could affect the result: those read during
+    /// the scan and the one at the root, even if it does not exist
+    pub ignore_files: Vec<PathBuf>,
+}
+
+/// How a discovered repository is laid out on disk
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoKind {
+    /// A working tree with its own `.git` directory, or a `.git` file
+    /// pointing at a separate git directory
+    Normal,
+    /// A repository without a working tree
+    Bare,
+    /// A linked working tree created by `git worktree add`
+    Worktree,
+    /// A submodule checkout whose git directory lives in its superproject
+    Submodule,
+}
+
+impl RepoKind {
+    pub const ALL: [RepoKind; 4] = [
+        RepoKind::Normal,
+        RepoKind::Bare,
+        RepoKind::Worktree,
+        RepoKind::Submodule,
+    ];
+
+    /// Classifies `path`, returning `None` if it is not a repository
+    pub fn detect(path: &Path) -> Option<RepoKind> {
+        let dot_git = path.join(".git");
+        match fs::metadata(&dot_git) {
+            Ok(metadata) if metadata.is_dir() => return Some(RepoKind::Normal),
+            Ok(_) => return Self::from_gitlink(path, &dot_git),
+            Err(_) => {}
+        }
+
+        // A bare repository is a git directory itself; `.git` directories
+        // inside working trees are never reported on their own
+        let is_bare = path.file_name().is_some_and(|name| name != ".git")
+            && path.join("HEAD").is_file()
+            && path.join("objects").is_dir()
+            && path.join("refs").is_dir();
+        is_bare.then_some(RepoKind::Bare)
+    }
+
+    /// Classifies a working tree from its `.git` file, which holds a
+    /// `gitdir: <path>` line
+    fn from_gitlink(path: &Path, dot_git: &Path) -> Option<RepoKind> {
+        let gitdir = read_gitlink(path, dot_git)?;
+
+        let parent_is = |name: &str| {
+            gitdir
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(|n| n == name)
+        };
+        if gitdir.join("commondir").is_file() || parent_is("worktrees") {
+            Some(RepoKind::Worktree)
+        } else if gitdir
+            .components()
+            .any(|c| c == Component::Normal("modules".as_ref()))
+        {
+            Some(RepoKind::Submodule)
+        } else {
+            Some(RepoKind::Normal)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkerConfig {
+    pub max_depth: Option<usize>,
+    pub follow_links: bool,
+    pub same_file_system: bool,
+    pub threads: usize,
+    pub max_repos: Option<usize>,
+    pub stop_at_git: bool,
+    /// Kinds of repository to report
+    pub kinds: Vec<RepoKind>,
+    /// Gitignore-style globs, relative to the root, of paths never descended into
+    pub excludes: Vec<String>,
+    /// Honour `.rkitignore` files found under the root
+    pub ignore_files: bool,
+}
+
+impl Default for WalkerConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(4),
+            follow_links: false,
+            same_file_system: true,
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            max_repos: None,
+            stop_at_git: true,
+            kinds: RepoKind::ALL.to_vec(),
+            excludes: Vec::new(),
+            ignore_files: true,
+        }
+    }
+}
+
+impl WalkerConfig {
+    /// Key identifying the settings that affect which repositories are found,
+    /// used to tell whether a cached index can answer a listing
+    pub fn scan_key(&self) -> String {
+        let mut kinds = self.kinds.clone();
+        kinds.sort();
+        kinds.dedup();
+        format!(
+            "depth={:?};links={};same_fs={};stop_at_git={};kinds={:?};excludes={:?};ignore_files={}",
+            self.max_depth,
+            self.follow_links,
+            self.same_file_system,
+            self.stop_at_git,
+            kinds,
+            self.excludes,
+            self.ignore_files
+        )
+    }
+}
+
+/// Builds the walker overrides skipping every path matching `excludes`
+pub fn build_excludes(root: &Path, excludes: &[String]) -> RkitResult<Override> {
+    let mut builder = OverrideBuilder::new(root);
+    for pattern in excludes {
+        // Override globs whitelist by default; negating them ignores instead
+        builder.add(&format!("!{}", pattern)).map_err(|e| {
+            RkitError::ConfigError(format!("Invalid exclude pattern '{}': {}", pattern, e))
+        })?;
+    }
+    builder
+        .build()
+        .map_err(|e| RkitError::ConfigError(format!("Failed to build exclude patterns: {}", e)))
+}
+
+/// A git repository found by a [`RepoScanner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredRepo {
+    /// Absolute path to the repository's working directory, or to the
+    /// repository itself if it is bare
+    pub path: PathBuf,
+    pub kind: RepoKind,
+}
+
+/// Result of a completed scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// Repositories in the order they were found
+    pub repos: Vec<DiscoveredRepo>,
+    pub metrics: PerformanceMetrics,
+}
+
+/// Parallel git repository discovery.
+///
+/// Repositories can be consumed as they are found with [`RepoScanner::scan_with`],
+/// collected with [`RepoScanner::scan`], or received over a channel from a
+/// background thread with [`RepoScanner::spawn`].
+#[derive(Debug, Clone, Default)]
+pub struct RepoScanner {
+    config: WalkerConfig,
+}
+
+impl RepoScanner {
+    pub fn new(config: WalkerConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &WalkerConfig {
+        &self.config
+    }
+
+    /// Scans `root`, calling `on_repo` from the walker threads for every
+    /// repository as soon as it is found
+    pub fn scan_with<F>(&self, root: &Path, on_repo: F) -> PerformanceMetrics
+    where
+        F: Fn(DiscoveredRepo) + Sync,
+    {
+        let config = &self.config;
+        let start = Instant::now();
+
+        // Build parallel walker using configured threads
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .max_depth(config.max_depth)
+            .follow_links(config.follow_links)
+            .same_file_system(config.same_file_system)
+            .threads(config.threads)
+            .git_ignore(false)
+            .ignore(false)
+            .parents(false);
+        if config.ignore_files {
+            builder.add_custom_ignore_filename(IGNORE_FILENAME);
+        }
+        if !config.excludes.is_empty() {
+            match build_excludes(root, &config.excludes) {
+                Ok(overrides) => {
+                    builder.overrides(overrides);
+                }
+                Err(e) => log::error!("Ignoring exclude patterns: {}", e),
+            }
+        }
+        let walker = builder.build_parallel();
+
+        let repo_count = AtomicUsize::new(0);
+        let limit_reached = AtomicBool::new(false);
+        let scanned_dirs = AtomicUsize::new(0);
+        let ignore_files = Mutex::new(Vec::new());
+        if config.ignore_files {
+            if let Ok(mut files) = ignore_files.lock() {
+                files.push(root.join(IGNORE_FILENAME));
+            }
+        }
+
+        walker.run(|| {
+            let repo_count = &repo_count;
+            let limit_reached = &limit_reached;
+            let scanned_dirs = &scanned_dirs;
+            let ignore_files = &ignore_files;
+            let on_repo = &on_repo;
+            Box::new(move |result| {
+                scanned_dirs.fetch_add(1, Ordering::Relaxed);
+                match result {
+                    Ok(entry) => {
+                        // Only classify directories
+                        if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+                            if let Some(kind) = RepoKind::detect(entry.path()) {
+                                // Never descend into the internals of a bare
+                                // repository, and treat excluded kinds as
+                                // repository boundaries all the same
+                                if !config.kinds.contains(&kind) {
+                                    return if config.stop_at_git || kind == RepoKind::Bare {
+                                        WalkState::Skip
+                                    } else {
+                                        WalkState::Continue
+                                    };
+                                }
+
+                                // Claim a slot first so concurrent threads never
+                                // report more than max_repos repositories
+                                let found = repo_count.fetch_add(1, Ordering::Relaxed) + 1;
+                                if let Some(max_repos) = config.max_repos {
+                                    if found > max_repos {
+                                        limit_reached.store(true, Ordering::Relaxed);
+                                        return WalkState::Quit;
+                                    }
+                                }
+
+                                on_repo(DiscoveredRepo {
+                                    path: entry.path().to_path_buf(),
+                                    kind,
+                                });
+
+                                if let Some(max_repos) = config.max_repos {
+                                    if found >= max_repos {
+                                        log::info!(
+                                            "Reached maximum number of repositories ({})",
+                                            max_repos
+                                        );
+                                        limit_reached.store(true, Ordering::Relaxed);
+                                        return WalkState::Quit;
+                                    }
+                                }
+
+                                if config.stop_at_git || kind == RepoKind::Bare {
+                                    return WalkState::Skip;
+                                }
+                            }
+
+                            // The walker reads the ignore file of every
+                            // directory it descends into
+                            let ignore_file = entry.path().join(IGNORE_FILENAME);
+                            if config.ignore_files && entry.depth() > 0 && ignore_file.is_file() {
+                                if let Ok(mut files) = ignore_files.lock() {
+                                    files.push(ignore_file);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => log::error!("Error walking directory: {}", e),
+                }
+                WalkState::Continue
+            })
+        });
+
+        let repo_count = repo_count.load(Ordering::Relaxed);
+        let metrics = PerformanceMetrics {
+            total_duration: start.elapsed(),
+            repo_count: config
+                .max_repos
+                .map_or(repo_count, |max| repo_count.min(max)),
+            scanned_dirs: scanned_dirs.load(Ordering::Relaxed),
+            limit_reached: limit_reached.load(Ordering::Relaxed),
+            ignore_files: ignore_files.into_inner().unwrap_or_default(),
+        };
+
+        log::info!(
+            "Scanned {} directories, found {} repositories in {:?}",
+            metrics.scanned_dirs,
+            metrics.repo_count,
+            metrics.total_duration
+        );
+
+        metrics
+    }
+
+    /// Scans `root` and collects every repository found
+    pub fn scan(&self, root: &Path) -> ScanResult {
+        let repos = Mutex::new(Vec::new());
+        let metrics = self.scan_with(root, |repo| {
+            if let Ok(mut repos) = repos.lock() {
+                repos.push(repo);
+            }
+        });
+
+        ScanResult {
+            repos: repos.into_inner().unwrap_or_default(),
+            metrics,
+        }
+    }
+
+    /// Scans `root` on a background thread, sending repositories over a
+    /// channel as they are found
+    pub fn spawn(&self, root: PathBuf) -> ScanHandle {
+        let (sender, receiver) = mpsc::channel();
+        let scanner = self.clone();
+        let handle = std::thread::spawn(move || {
+            scanner.scan_with(&root, |repo| {
+                // The receiver may have been dropped; keep walking regardless
+                let _ = sender.send(repo);
+            })
+        });
+
+        ScanHandle { receiver, handle }
+    }
+}
+
+/// Handle to a scan running on a background thread. Iterating yields
+/// repositories as they are found; [`ScanHandle::finish`] waits for the scan
+/// to complete and returns its metrics.
+pub struct ScanHandle {
+    receiver: Receiver<DiscoveredRepo>,
+    handle: JoinHandle<PerformanceMetrics>,
+}
+
+impl ScanHandle {
+    /// Waits for the scan to complete and returns its metrics
+    pub fn finish(self) -> PerformanceMetrics {
+        drop(self.receiver);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+impl Iterator for ScanHandle {
+    type Item = DiscoveredRepo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn create_git_repo(path: &Path) {
+        fs::create_dir_all(path.join(".git")).unwrap();
+    }
+
+    fn sorted_paths(repos: &[DiscoveredRepo]) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = repos.iter().map(|r| r.path.clone()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_scan_finds_repos() {
+        let dir = tempdir().unwrap();
+        let repo1 = dir.path().join("repo1");
+        let repo2 = dir.path().join("org").join("repo2");
+        create_git_repo(&repo1);
+        create_git_repo(&repo2);
+        fs::create_dir_all(dir.path().join("not_a_repo")).unwrap();
+
+        let result = RepoScanner::default().scan(dir.path());
+        assert_eq!(sorted_paths(&result.repos), vec![repo2, repo1]);
+        assert_eq!(result.metrics.repo_count, 2);
+        assert!(!result.metrics.limit_reached);
+        assert!(result.metrics.scanned_dirs > 0);
+    }
+
+    #[test]
+    fn test_scan_max_repos() {
+        let dir = tempdir().unwrap();
+        for name in ["repo1", "repo2", "repo3"] {
+            create_git_repo(&dir.path().join(name));
+        }
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            max_repos: Some(2),
+            ..Default::default()
+        });
+        let result = scanner.scan(dir.path());
+        assert_eq!(result.repos.len(), 2);
+        assert_eq!(result.metrics.repo_count, 2);
+        assert!(result.metrics.limit_reached);
+    }
+
+    #[test]
+    fn test_scan_max_depth() {
+        let dir = tempdir().unwrap();
+        let repo1 = dir.path().join("level1").join("repo1");
+        let repo2 = dir.path().join("level1").join("level2").join("repo2");
+        create_git_repo(&repo1);
+        create_git_repo(&repo2);
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        });
+        let result = scanner.scan(dir.path());
+        assert_eq!(sorted_paths(&result.repos), vec![repo1]);
+    }
+
+    #[test]
+    fn test_scan_stop_at_git() {
+        let dir = tempdir().unwrap();
+        let outer = dir.path().join("outer");
+        let inner = outer.join("inner");
+        create_git_repo(&outer);
+        create_git_repo(&inner);
+
+        let result = RepoScanner::default().scan(dir.path());
+        assert_eq!(sorted_paths(&result.repos), vec![outer.clone()]);
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            stop_at_git: false,
+            ..Default::default()
+        });
+        let result = scanner.scan(dir.path());
+        assert_eq!(sorted_paths(&result.repos), vec![outer, inner]);
+    }
+
+    #[test]
+    fn test_repo_kind_detection() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+
+        let normal = root.join("normal");
+        create_git_repo(&normal);
+        fs::create_dir_all(normal.join(".git").join("worktrees").join("wt")).unwrap();
+        fs::write(
+            normal
+                .join(".git")
+                .join("worktrees")
+                .join("wt")
+                .join("commondir"),
+            "../..\n",
+        )
+        .unwrap();
+        fs::create_dir_all(normal.join(".git").join("modules").join("sub")).unwrap();
+
+        let worktree = root.join("wt");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            "gitdir: ../normal/.git/worktrees/wt\n",
+        )
+        .unwrap();
+
+        let submodule = normal.join("sub");
+        fs::create_dir_all(&submodule).unwrap();
+        fs::write(submodule.join(".git"), "gitdir: ../.git/modules/sub\n").unwrap();
+
+        let separate = root.join("separate");
+        fs::create_dir_all(&separate).unwrap();
+        fs::write(separate.join(".git"), "gitdir: /srv/git/separate.git\n").unwrap();
+
+        let bare = root.join("bare.git");
+        fs::create_dir_all(bare.join("objects")).unwrap();
+        fs::create_dir_all(bare.join("refs")).unwrap();
+        fs::write(bare.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let not_repo = root.join("plain");
+        fs::create_dir_all(&not_repo).unwrap();
+        fs::write(not_repo.join(".git"), "not a gitlink\n").unwrap();
+
+        assert_eq!(RepoKind::detect(&normal), Some(RepoKind::Normal));
+        assert_eq!(RepoKind::detect(&worktree), Some(RepoKind::Worktree));
+        assert_eq!(RepoKind::detect(&submodule), Some(RepoKind::Submodule));
+        assert_eq!(RepoKind::detect(&separate), Some(RepoKind::Normal));
+        assert_eq!(RepoKind::detect(&bare), Some(RepoKind::Bare));
+        assert_eq!(RepoKind::detect(&normal.join(".git")), None);
+        assert_eq!(RepoKind::detect(&not_repo), None);
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            stop_at_git: false,
+            ..Default::default()
+        });
+        let mut found: Vec<(PathBuf, RepoKind)> = scanner
+            .scan(root)
+            .repos
+            .into_iter()
+            .map(|r| (r.path, r.kind))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (bare.clone(), RepoKind::Bare),
+                (normal.clone(), RepoKind::Normal),
+                (submodule, RepoKind::Submodule),
+                (separate.clone(), RepoKind::Normal),
+                (worktree.clone(), RepoKind::Worktree),
+            ]
+        );
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            kinds: vec![RepoKind::Normal],
+            ..Default::default()
+        });
+        assert_eq!(
+            sorted_paths(&scanner.scan(root).repos),
+            vec![normal, separate]
+        );
+    }
+
+    #[test]
+    fn test_scan_excludes_and_ignore_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let kept = root.join("github.com").join("org").join("kept");
+        let vendored = root.join("app").join("node_modules").join("dep");
+        let archived = root.join("archive").join("old");
+        let ignored = root.join("scratch").join("tmp");
+        for repo in [&kept, &vendored, &archived, &ignored] {
+            create_git_repo(repo);
+        }
+        fs::write(root.join(IGNORE_FILENAME), "/archive\n").unwrap();
+        fs::write(root.join("scratch").join(IGNORE_FILENAME), "tmp/\n").unwrap();
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            excludes: vec!["node_modules".to_string()],
+            ..Default::default()
+        });
+        let result = scanner.scan(root);
+        assert_eq!(sorted_paths(&result.repos), vec![kept.clone()]);
+        let mut ignore_files = result.metrics.ignore_files;
+        ignore_files.sort();
+        assert_eq!(
+            ignore_files,
+            vec![
+                root.join(IGNORE_FILENAME),
+                root.join("scratch").join(IGNORE_FILENAME)
+            ]
+        );
+
+        let scanner = RepoScanner::new(WalkerConfig {
+            ignore_files: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            sorted_paths(&scanner.scan(root).repos),
+            vec![vendored, archived, kept, ignored]
+        );
+    }
+
+    #[test]
+    fn test_build_excludes_invalid() {
+        let result = build_excludes(Path::new("/projects"), &["a/[".to_string()]);
+        assert!(matches!(result, Err(RkitError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_spawn_streams_repos() {
+        let dir = tempdir().unwrap();
+        let repo1 = dir.path().join("repo1");
+        create_git_repo(&repo1);
+
+        let mut handle = RepoScanner::default().spawn(dir.path().to_path_buf());
+        let repos: Vec<DiscoveredRepo> = handle.by_ref().collect();
+        let metrics = handle.finish();
+        assert_eq!(sorted_paths(&repos), vec![repo1]);
+        assert_eq!(metrics.repo_count, 1);
+    }
+
+    #[test]
+    fn test_walker_config_scan_key() {
+        let config = WalkerConfig::default();
+        let deeper = WalkerConfig {
+            max_depth: Some(8),
+            ..Default::default()
+        };
+        let more_threads = WalkerConfig {
+            threads: config.threads + 1,
+            ..Default::default()
+        };
+        assert_ne!(config.scan_key(), deeper.scan_key());
+        assert_eq!(config.scan_key(), more_threads.scan_key());
+    }
+
+    #[test]
+    fn test_walker_config_default() {
+        let config = WalkerConfig::default();
+        assert_eq!(config.max_depth, Some(4));
+        assert!(!config.follow_links);
+        assert!(config.same_file_system);
+        assert!(config.threads > 0);
+        assert_eq!(config.max_repos, None);
+        assert!(config.stop_at_git);
+        assert_eq!(config.kinds, RepoKind::ALL);
+        assert!(config.excludes.is_empty());
+        assert!(config.ignore_files);
+    }
+}
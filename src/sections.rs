@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ignore::WalkBuilder;
+
+use crate::config::SectionKind;
+use crate::error::{RkitError, RkitResult};
+use crate::git::{
+    common_dir, git_dir, read_head, read_index, read_reflog, resolve_ref, GitConfig, Head,
+    IndexEntry, GITLINK_MODE,
+};
+use crate::scanner::RepoKind;
+
+/// Number of reflog entries shown by the `reflog` section
+const REFLOG_ENTRIES: usize = 10;
+/// Number of languages shown by the `languages` section
+const TOP_LANGUAGES: usize = 8;
+
+/// Renders a built-in section from the repository's files and `.git`
+/// directory, without running git
+pub fn render(kind: SectionKind, repo: &Path) -> RkitResult<String> {
+    let git_dir = git_dir(repo).ok_or_else(|| {
+        RkitError::GitError(format!("{} is not a git repository", repo.display()))
+    })?;
+    match kind {
+        SectionKind::Branch => branch(&git_dir),
+        SectionKind::Status => status(worktree(repo)?, &git_dir),
+        SectionKind::Reflog => Ok(reflog(&git_dir)),
+        SectionKind::Readme => readme(repo),
+        SectionKind::Remotes => Ok(remotes(&git_dir)),
+        SectionKind::Languages => Ok(languages(worktree(repo)?)),
+        SectionKind::Size => Ok(size(repo, &git_dir)),
+    }
+}
+
+fn worktree(repo: &Path) -> RkitResult<&Path> {
+    if RepoKind::detect(repo) == Some(RepoKind::Bare) {
+        return Err(RkitError::GitError(format!(
+            "{} is a bare repository without a working tree",
+            repo.display()
+        )));
+    }
+    Ok(repo)
+}
+
+fn short(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+/// Upstream of a branch as `remote/branch`, from its `branch.*` config
+fn upstream(config: &GitConfig, branch: &str) -> Option<(String, String)> {
+    let remote = config.get("branch", Some(branch), "remote")?;
+    let merge = config.get("branch", Some(branch), "merge")?;
+    let merge = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    if remote == "." {
+        Some((merge.to_string(), format!("refs/heads/{}", merge)))
+    } else {
+        Some((
+            format!("{}/{}", remote, merge),
+            format!("refs/remotes/{}/{}", remote, merge),
+        ))
+    }
+}
+
+fn branch(git_dir: &Path) -> RkitResult<String> {
+    let head = read_head(git_dir)
+        .ok_or_else(|| RkitError::GitError(format!("Cannot read HEAD in {}", git_dir.display())))?;
+    let mut out = String::new();
+    match head {
+        Head::Branch(name) => {
+            let _ = writeln!(out, "{}", name);
+            let commit = resolve_ref(git_dir, &format!("refs/heads/{}", name));
+            match &commit {
+                Some(commit) => {
+                    let _ = writeln!(out, "Commit: {}", short(commit));
+                }
+                None => out.push_str("No commits yet\n"),
+            }
+            if let Some((upstream, reference)) = upstream(&GitConfig::load(git_dir), &name) {
+                let state = match resolve_ref(git_dir, &reference) {
+                    Some(remote) if Some(&remote) == commit.as_ref() => "up to date",
+                    Some(_) => "differs",
+                    None => "gone",
+                };
+                let _ = writeln!(out, "Upstream: {} ({})", upstream, state);
+            }
+        }
+        Head::Detached(commit) => {
+            let _ = writeln!(out, "HEAD detached at {}", short(&commit));
+        }
+    }
+    Ok(out)
+}
+
+/// Path relative to the working tree with `/` separators, as git stores it
+fn index_path(relative: &Path) -> String {
+    relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn mtime(metadata: &fs::Metadata) -> Option<Duration> {
+    metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()
+}
+
+/// Modification time git recorded for an index entry
+fn entry_mtime(entry: &IndexEntry) -> Duration {
+    Duration::new(entry.mtime as u64, entry.mtime_nsec)
+}
+
+/// Whether a file still has the modification time recorded in the index.
+/// The index keeps the low 32 bits of the seconds, and no nanoseconds when
+/// git was built without them.
+fn same_mtime(file: Duration, entry: &IndexEntry) -> bool {
+    file.as_secs() as u32 == entry.mtime
+        && (entry.mtime_nsec == 0 || file.subsec_nanos() == entry.mtime_nsec)
+}
+
+/// Short status of the working tree against the index, in the format of
+/// `git status --short --branch`. Files are compared by size and
+/// modification time without reading them, so staged changes are not listed
+/// and a file touched without changing shows as modified. Like git, an entry
+/// written no earlier than the index itself is racy: it may have changed
+/// without its size or time changing, so it is shown as ` ?` instead of
+/// being reported clean.
+fn status(repo: &Path, git_dir: &Path) -> RkitResult<String> {
+    let mut out = String::new();
+    match read_head(git_dir) {
+        Some(Head::Branch(name)) => {
+            let _ = write!(out, "## {}", name);
+            if let Some((upstream, _)) = upstream(&GitConfig::load(git_dir), &name) {
+                let _ = write!(out, "...{}", upstream);
+            }
+            out.push('\n');
+        }
+        Some(Head::Detached(_)) => out.push_str("## HEAD (no branch)\n"),
+        None => {}
+    }
+
+    // A repository without commits may not have an index yet
+    let entries = read_index(git_dir).unwrap_or_default();
+    let index_mtime = fs::metadata(git_dir.join("index"))
+        .ok()
+        .and_then(|metadata| mtime(&metadata));
+    let mut changes: BTreeMap<String, &str> = BTreeMap::new();
+    let mut tracked: HashSet<&str> = HashSet::new();
+    let mut tracked_dirs: HashSet<&str> = HashSet::new();
+    for entry in &entries {
+        tracked.insert(&entry.path);
+        let mut dir = entry.path.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            if !tracked_dirs.insert(parent) {
+                break;
+            }
+            dir = parent;
+        }
+
+        if entry.stage != 0 {
+            changes.insert(entry.path.clone(), "UU");
+            continue;
+        }
+        if entry.skip_worktree || entry.mode == GITLINK_MODE {
+            continue;
+        }
+        match fs::symlink_metadata(repo.join(&entry.path)) {
+            Err(_) => {
+                changes.insert(entry.path.clone(), " D");
+            }
+            Ok(metadata) => {
+                // The index keeps the low 32 bits of sizes
+                let size_changed = metadata.len() as u32 != entry.size;
+                let time_changed = !mtime(&metadata).is_some_and(|t| same_mtime(t, entry));
+                let racy = index_mtime.is_none_or(|index| entry_mtime(entry) >= index);
+                if size_changed || time_changed {
+                    changes.insert(entry.path.clone(), " M");
+                } else if racy {
+                    changes.insert(entry.path.clone(), " ?");
+                }
+            }
+        }
+    }
+
+    let walker = WalkBuilder::new(repo)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| {
+            // Skip git directories, including those of nested repositories
+            entry.file_name() != ".git"
+                && (entry.depth() == 0
+                    || !entry.file_type().is_some_and(|t| t.is_dir())
+                    || !entry.path().join(".git").exists())
+        })
+        .build();
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        if !entry
+            .file_type()
+            .is_some_and(|t| t.is_file() || t.is_symlink())
+        {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(repo) else {
+            continue;
+        };
+        let path = index_path(relative);
+        if tracked.contains(path.as_str()) {
+            continue;
+        }
+        // Like git, report the outermost directory without tracked files
+        // instead of every file in it
+        let mut end = 0;
+        let untracked = loop {
+            match path[end..].find('/') {
+                Some(i) => {
+                    end += i;
+                    if !tracked_dirs.contains(&path[..end]) {
+                        break format!("{}/", &path[..end]);
+                    }
+                    end += 1;
+                }
+                None => break path.clone(),
+            }
+        };
+        changes.insert(untracked, "??");
+    }
+
+    if changes.is_empty() {
+        out.push_str("Working tree clean\n");
+    }
+    for (path, code) in changes {
+        let _ = writeln!(out, "{} {}", code, path);
+    }
+    Ok(out)
+}
+
+/// How long ago `time` was, e.g. `3 hours ago`
+fn ago(now: u64, time: u64) -> String {
+    let secs = now.saturating_sub(time);
+    let (count, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        86400..2_592_000 => (secs / 86400, "day"),
+        2_592_000..31_536_000 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+/// Recent movements of HEAD from the reflog: commits, checkouts, merges.
+/// This is not the commit history: commits that reached the branch without
+/// moving HEAD are missing, and a fresh clone has a single entry.
+fn reflog(git_dir: &Path) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let entries = read_reflog(git_dir);
+    if entries.is_empty() {
+        return "No history yet\n".to_string();
+    }
+    let mut out = String::new();
+    for entry in entries.iter().take(REFLOG_ENTRIES) {
+        let _ = writeln!(
+            out,
+            "{}  {:<14}  {}",
+            short(&entry.commit),
+            ago(now, entry.time),
+            entry.message
+        );
+    }
+    out
+}
+
+fn readme(repo: &Path) -> RkitResult<String> {
+    let mut candidates: Vec<(String, std::path::PathBuf)> = fs::read_dir(repo)
+        .map_err(|e| RkitError::FileReadError {
+            path: repo.to_path_buf(),
+            source: e,
+        })?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            name.starts_with("readme").then(|| (name, entry.path()))
+        })
+        .collect();
+    // Prefer README.md, then the shortest name such as README over README.ja.md
+    candidates.sort_by_key(|(name, _)| (name != "readme.md", name.len(), name.clone()));
+
+    let Some((_, path)) = candidates.into_iter().next() else {
+        return Ok("No README found\n".to_string());
+    };
+    fs::read_to_string(&path).map_err(|e| RkitError::FileReadError { path, source: e })
+}
+
+/// Configured remotes with their fetch and push URLs, like `git remote -v`
+fn remotes(git_dir: &Path) -> String {
+    let config = GitConfig::load(git_dir);
+    let names = config.subsections("remote");
+    if names.is_empty() {
+        return "No remotes\n".to_string();
+    }
+    let mut out = String::new();
+    for name in names {
+        let url = config.get("remote", Some(name), "url").unwrap_or_default();
+        let push = config.get("remote", Some(name), "pushurl").unwrap_or(url);
+        let _ = writeln!(out, "{}\t{} (fetch)", name, url);
+        let _ = writeln!(out, "{}\t{} (push)", name, push);
+    }
+    out
+}
+
+/// Language of a file from its name or extension
+fn language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Dockerfile" => return Some("Dockerfile"),
+        "Makefile" | "makefile" | "GNUmakefile" => return Some("Makefile"),
+        _ => {}
+    }
+    let language = match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "rs" => "Rust",
+        "py" | "pyi" => "Python",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "scala" => "Scala",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "C++",
+        "cs" => "C#",
+        "fs" | "fsx" => "F#",
+        "m" | "mm" => "Objective-C",
+        "swift" => "Swift",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "pl" | "pm" => "Perl",
+        "lua" => "Lua",
+        "r" => "R",
+        "dart" => "Dart",
+        "ex" | "exs" => "Elixir",
+        "erl" | "hrl" => "Erlang",
+        "hs" => "Haskell",
+        "ml" | "mli" => "OCaml",
+        "clj" | "cljs" | "cljc" => "Clojure",
+        "zig" => "Zig",
+        "nim" => "Nim",
+        "jl" => "Julia",
+        "sh" | "bash" | "zsh" | "fish" => "Shell",
+        "ps1" | "psm1" => "PowerShell",
+        "html" | "htm" => "HTML",
+        "css" => "CSS",
+        "scss" | "sass" | "less" => "SCSS",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "sql" => "SQL",
+        "nix" => "Nix",
+        "tf" | "hcl" => "HCL",
+        "proto" => "Protocol Buffers",
+        "md" | "markdown" => "Markdown",
+        "yaml" | "yml" => "YAML",
+        "toml" => "TOML",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Share of the working tree's bytes per language, ignoring files excluded
+/// by `.gitignore`
+fn languages(repo: &Path) -> String {
+    let mut totals: HashMap<&str, (u64, usize)> = HashMap::new();
+    let walker = WalkBuilder::new(repo)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Some(language) = language(entry.path()) else {
+            continue;
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+        let total = totals.entry(language).or_default();
+        total.0 += size;
+        total.1 += 1;
+    }
+
+    let all: u64 = totals.values().map(|(bytes, _)| bytes).sum();
+    if totals.is_empty() {
+        return "No source files recognised\n".to_string();
+    }
+    let mut ranked: Vec<(&str, (u64, usize))> = totals.into_iter().collect();
+    ranked.sort_by(|(a, (bytes_a, _)), (b, (bytes_b, _))| bytes_b.cmp(bytes_a).then(a.cmp(b)));
+
+    let mut out = String::new();
+    for (language, (bytes, files)) in ranked.into_iter().take(TOP_LANGUAGES) {
+        let share = if all == 0 {
+            0.0
+        } else {
+            bytes as f64 * 100.0 / all as f64
+        };
+        let plural = if files == 1 { "" } else { "s" };
+        let _ = writeln!(
+            out,
+            "{:<18} {:>5.1}%  {} file{}",
+            language, share, files, plural
+        );
+    }
+    out
+}
+
+/// Total size and number of files under `path`, not descending into `.git`
+/// directories when `skip_git` is set
+fn dir_size(path: &Path, skip_git: bool) -> (u64, usize) {
+    let walker = WalkBuilder::new(path)
+        .standard_filters(false)
+        .filter_entry(move |entry| !skip_git || entry.file_name() != ".git")
+        .build();
+    walker
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .fold((0, 0), |(bytes, files), entry| {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+            (bytes + size, files + 1)
+        })
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Disk usage of the working tree and the git directory
+fn size(repo: &Path, git_dir: &Path) -> String {
+    let mut out = String::new();
+    if RepoKind::detect(repo) != Some(RepoKind::Bare) {
+        let (bytes, files) = dir_size(repo, true);
+        let _ = writeln!(
+            out,
+            "Working tree   {:>10}  ({} files)",
+            format_size(bytes),
+            files
+        );
+    }
+    let (bytes, _) = dir_size(&common_dir(git_dir), false);
+    let _ = writeln!(out, "Git directory  {:>10}", format_size(bytes));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sections() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        git(&repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("README.md"), "# Repo\n").unwrap();
+        fs::write(repo.join("src/lib.rs"), "pub fn answer() -> u32 { 42 }\n").unwrap();
+        fs::write(repo.join("src/old.rs"), "").unwrap();
+        fs::write(repo.join("build.sh"), "#!/bin/sh\n").unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "Initial commit"]);
+        git(
+            &repo,
+            &["remote", "add", "origin", "https://github.com/org/repo.git"],
+        );
+
+        let branch = render(SectionKind::Branch, &repo).unwrap();
+        assert!(branch.starts_with("main\nCommit: "));
+
+        // Untouched working tree
+        let status = render(SectionKind::Status, &repo).unwrap();
+        assert_eq!(status, "## main\nWorking tree clean\n");
+
+        fs::write(repo.join("src/lib.rs"), "pub fn answer() -> u64 { 42 }\n\n").unwrap();
+        fs::remove_file(repo.join("src/old.rs")).unwrap();
+        fs::create_dir_all(repo.join("docs/guide")).unwrap();
+        fs::write(repo.join("docs/guide/intro.md"), "").unwrap();
+        fs::write(repo.join("src/new.rs"), "").unwrap();
+        fs::create_dir_all(repo.join("target")).unwrap();
+        fs::write(repo.join("target/out"), "").unwrap();
+        let status = render(SectionKind::Status, &repo).unwrap();
+        assert_eq!(
+            status,
+            "## main\n?? docs/\n M src/lib.rs\n?? src/new.rs\n D src/old.rs\n"
+        );
+
+        let reflog = render(SectionKind::Reflog, &repo).unwrap();
+        assert!(reflog.contains("just now"));
+        assert!(reflog.contains("commit (initial): Initial commit"));
+
+        assert_eq!(render(SectionKind::Readme, &repo).unwrap(), "# Repo\n");
+        assert_eq!(
+            render(SectionKind::Remotes, &repo).unwrap(),
+            "origin\thttps://github.com/org/repo.git (fetch)\n\
+             origin\thttps://github.com/org/repo.git (push)\n"
+        );
+
+        let languages = render(SectionKind::Languages, &repo).unwrap();
+        assert!(languages.starts_with("Rust"));
+        assert!(languages.contains("Shell"));
+
+        let size = render(SectionKind::Size, &repo).unwrap();
+        assert!(size.starts_with("Working tree"));
+        assert!(size.contains("Git directory"));
+
+        assert!(render(SectionKind::Branch, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_status_racy_entries() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("a.txt"), "one").unwrap();
+        fs::write(repo.join("b.txt"), "two").unwrap();
+        git(&repo, &["add", "."]);
+
+        // An index older than its entries cannot vouch for them
+        let index = fs::File::options()
+            .write(true)
+            .open(repo.join(".git/index"))
+            .unwrap();
+        index
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        fs::write(repo.join("b.txt"), "three").unwrap();
+
+        let status = render(SectionKind::Status, &repo).unwrap();
+        assert_eq!(status, "## main\n ? a.txt\n M b.txt\n");
+    }
+
+    #[test]
+    fn test_ago_and_format_size() {
+        assert_eq!(ago(1000, 1000), "just now");
+        assert_eq!(ago(1000 + 60, 1000), "1 minute ago");
+        assert_eq!(ago(3 * 3600, 0), "3 hours ago");
+        assert_eq!(ago(400 * 86400, 0), "1 year ago");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
@@ -0,0 +1,175 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::git::{head_branch, origin_url};
+use crate::layout::Layouts;
+use crate::workspace::Workspace;
+
+/// Prefix of placeholders replaced with an environment variable, as in
+/// `{ENV:HOME}`
+const ENV_PREFIX: &str = "ENV:";
+
+/// Values substituted for `{VARIABLE}` placeholders in configured commands
+/// such as `rview`. Git metadata is only read when a template uses it.
+#[derive(Debug)]
+pub struct TemplateVars {
+    repo: PathBuf,
+    name: String,
+    domain: String,
+    org: String,
+    rel: PathBuf,
+    root: PathBuf,
+    branch: OnceLock<String>,
+    remote_url: OnceLock<String>,
+}
+
+impl TemplateVars {
+    /// Variables of a repository outside any workspace: `{REL}` is its
+    /// directory name and `{ROOT}` the directory containing it
+    pub fn new(repo: &Path) -> Self {
+        let name = repo
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            repo: repo.to_path_buf(),
+            rel: PathBuf::from(&name),
+            name,
+            domain: String::new(),
+            org: String::new(),
+            root: repo.parent().map(Path::to_path_buf).unwrap_or_default(),
+            branch: OnceLock::new(),
+            remote_url: OnceLock::new(),
+        }
+    }
+
+    /// Variables of a repository in `workspace`. `{DOMAIN}`, `{ORG}` and
+    /// `{NAME}` are recovered from its path with the configured layouts, and
+    /// are empty (or the directory name) when the path does not follow them.
+    pub fn for_repo(workspace: &Workspace, layouts: &Layouts, repo: &Path) -> Self {
+        let mut vars = Self::new(repo);
+        if let Some(root) = workspace.root_of(repo) {
+            vars.root = root.path.clone();
+            vars.rel = workspace.relative(repo).to_path_buf();
+            if let Some(found) = layouts.extract(&vars.rel) {
+                vars.domain = found.domain.unwrap_or_default();
+                vars.org = found.org.unwrap_or_default();
+                if let Some(name) = found.repo {
+                    vars.name = name;
+                }
+            }
+        }
+        vars
+    }
+
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    /// Value of the placeholder `name`, or `None` if it is not a variable.
+    /// Unset environment variables are empty.
+    pub fn get(&self, name: &str) -> Option<String> {
+        if let Some(var) = name.strip_prefix(ENV_PREFIX) {
+            return Some(env::var(var).unwrap_or_default());
+        }
+        let value = match name {
+            "REPO" => self.repo.to_string_lossy().to_string(),
+            "NAME" => self.name.clone(),
+            "DOMAIN" => self.domain.clone(),
+            "ORG" => self.org.clone(),
+            "REL" => self.rel.to_string_lossy().to_string(),
+            "ROOT" => self.root.to_string_lossy().to_string(),
+            "BRANCH" => self
+                .branch
+                .get_or_init(|| head_branch(&self.repo).unwrap_or_default())
+                .clone(),
+            "REMOTE_URL" => self
+                .remote_url
+                .get_or_init(|| origin_url(&self.repo).unwrap_or_default())
+                .clone(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Replaces every placeholder in `template`. Braces that are not a known
+    /// placeholder, like those of an `awk` program, are left as they are.
+    pub fn expand(&self, template: &str) -> String {
+        self.expand_with(template, str::to_string)
+    }
+
+    /// Like [`expand`](Self::expand), passing every value through `quote`
+    pub fn expand_with(&self, template: &str, quote: impl Fn(&str) -> String) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let placeholder = after
+                .find('}')
+                .and_then(|end| self.get(&after[..end]).map(|value| (end, value)));
+            match placeholder {
+                Some((end, value)) => {
+                    result.push_str(&quote(&value));
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = after;
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Layout, DEFAULT_LAYOUT};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_workspace_vars() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("projects");
+        let repo = root.join("gitlab.com/group/sub/api");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let workspace = Workspace::single(&root, None);
+        let layouts = Layouts::new(Layout::parse(DEFAULT_LAYOUT).unwrap());
+        let vars = TemplateVars::for_repo(&workspace, &layouts, &repo);
+        assert_eq!(
+            vars.expand("https://{DOMAIN}/{ORG}/{NAME}/-/tree/{BRANCH}"),
+            "https://gitlab.com/group/sub/api/-/tree/main"
+        );
+        assert_eq!(
+            vars.expand("{ROOT} {REL}"),
+            format!("{} gitlab.com/group/sub/api", root.display())
+        );
+        assert_eq!(vars.expand("{REMOTE_URL}"), "");
+
+        let outside = TemplateVars::new(&dir.path().join("scratch"));
+        assert_eq!(outside.expand("{NAME}|{DOMAIN}|{REL}"), "scratch||scratch");
+    }
+
+    #[test]
+    fn test_expand() {
+        let vars = TemplateVars::new(Path::new("/srv/my repo"));
+        assert_eq!(
+            vars.expand("awk '{print $1}' {REPO}/x {UNKNOWN} {{NAME}}"),
+            "awk '{print $1}' /srv/my repo/x {UNKNOWN} {my repo}"
+        );
+        assert_eq!(
+            vars.expand_with("cd {REPO}", |value| format!("'{}'", value)),
+            "cd '/srv/my repo'"
+        );
+        assert_eq!(vars.expand("{ENV:RKIT_SURELY_UNSET}|{REPO"), "|{REPO");
+        let path = env::var("PATH").unwrap_or_default();
+        assert_eq!(vars.expand("{ENV:PATH}"), path);
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by unit tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs git in `dir` with a fixed identity, failing the test if it fails
+pub(crate) fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=rkit", "-c", "user.email=rkit@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Creates an empty bare repository at `remotes/org/repo.git` under `dir`,
+/// to clone from
+pub(crate) fn bare_remote(dir: &Path) -> PathBuf {
+    let remote = dir.join("remotes").join("org").join("repo.git");
+    fs::create_dir_all(&remote).unwrap();
+    git(&remote, &["init", "-q", "--bare"]);
+    remote
+}
@@ -0,0 +1,320 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::{get_cache_path, lock_file, write_atomic, LOCK_EXTENSION};
+use crate::error::{RkitError, RkitResult};
+
+/// Name of the usage store, kept next to `cache.json`
+const USAGE_FILE: &str = "usage.json";
+
+/// Once the ranks of all repositories add up to more than this, every rank
+/// is aged so rarely used repositories are eventually forgotten
+const MAX_TOTAL_RANK: f64 = 1000.0;
+const AGING_FACTOR: f64 = 0.9;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// How a single repository has been used
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepoUsage {
+    /// Last time the repository was visited (seconds since epoch)
+    pub last_visited: u64,
+    /// Number of visits, reduced as the store ages
+    #[serde(default = "default_rank")]
+    pub rank: f64,
+}
+
+fn default_rank() -> f64 {
+    1.0
+}
+
+impl Default for RepoUsage {
+    fn default() -> Self {
+        Self {
+            last_visited: 0,
+            rank: 0.0,
+        }
+    }
+}
+
+impl RepoUsage {
+    /// Score combining how often and how recently the repository was
+    /// visited, weighted like zoxide: visits in the last hour count four
+    /// times, in the last day twice, in the last week half and older ones a
+    /// quarter
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_visited);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * weight
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageData {
+    #[serde(default)]
+    repos: HashMap<PathBuf, RepoUsage>,
+}
+
+/// Persistent record of how often and when repositories were visited. Unlike
+/// cache entries, usage does not expire with a TTL; it is kept until the
+/// repository is gone or its rank has aged away.
+#[derive(Debug)]
+pub struct Usage {
+    path: PathBuf,
+    repos: HashMap<PathBuf, RepoUsage>,
+}
+
+impl Usage {
+    /// Path of the default usage store, next to the cache file
+    pub fn default_path() -> PathBuf {
+        match get_cache_path() {
+            Ok(cache_path) => cache_path.with_file_name(USAGE_FILE),
+            Err(e) => {
+                log::warn!("Failed to get cache path: {}", e);
+                env::temp_dir().join("rkit").join(USAGE_FILE)
+            }
+        }
+    }
+
+    /// Loads the usage store next to the cache file
+    pub fn load_default() -> Self {
+        Self::load(&Self::default_path())
+    }
+
+    /// Loads a usage store, starting empty if it is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        let repos = match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str::<UsageData>(&contents) {
+                Ok(data) => data.repos,
+                Err(e) => {
+                    log::warn!("Failed to parse usage file {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: path.to_path_buf(),
+            repos,
+        }
+    }
+
+    pub fn get(&self, repo: &Path) -> Option<&RepoUsage> {
+        self.repos.get(repo)
+    }
+
+    /// Last time `repo` was visited (seconds since epoch)
+    pub fn last_visited(&self, repo: &Path) -> Option<u64> {
+        self.get(repo).map(|usage| usage.last_visited)
+    }
+
+    /// Frecency of `repo` at the current time
+    pub fn frecency(&self, repo: &Path) -> Option<f64> {
+        self.get(repo).map(|usage| usage.frecency(now()))
+    }
+
+    /// Every recorded repository with its frecency, highest first
+    pub fn ranked(&self) -> Vec<(&Path, f64)> {
+        let now = now();
+        let mut ranked: Vec<(&Path, f64)> = self
+            .repos
+            .iter()
+            .map(|(repo, usage)| (repo.as_path(), usage.frecency(now)))
+            .collect();
+        ranked.sort_by(|(a, score_a), (b, score_b)| score_b.total_cmp(score_a).then(a.cmp(b)));
+        ranked
+    }
+
+    /// Records a visit to `repo` at the current time
+    pub fn record(&mut self, repo: &Path) {
+        let usage = self.repos.entry(repo.to_path_buf()).or_default();
+        usage.last_visited = now();
+        usage.rank += 1.0;
+        self.age();
+    }
+
+    fn age(&mut self) {
+        let total: f64 = self.repos.values().map(|usage| usage.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for usage in self.repos.values_mut() {
+                usage.rank *= AGING_FACTOR;
+            }
+            self.repos.retain(|_, usage| usage.rank >= 1.0);
+        }
+    }
+
+    /// Loads the store at `path`, applies `change` and saves it. The store is
+    /// locked from loading until it is saved, so concurrent rkit invocations
+    /// do not drop each other's changes.
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self)) -> RkitResult<()> {
+        create_parent(path)?;
+        let _lock = lock_file(path).map_err(|e| RkitError::FileWriteError {
+            path: path.with_extension(LOCK_EXTENSION),
+            source: e,
+        })?;
+
+        let mut usage = Self::load(path);
+        change(&mut usage);
+        usage.save()
+    }
+
+    /// Saves the store, dropping repositories that no longer exist. Use
+    /// [`update`](Self::update) to change a store other invocations may be
+    /// using.
+    pub fn save(&mut self) -> RkitResult<()> {
+        self.repos.retain(|repo, _| repo.exists());
+
+        create_parent(&self.path)?;
+        let data = UsageData {
+            repos: self.repos.clone(),
+        };
+        write_atomic(&self.path, &serde_json::to_string(&data)?).map_err(|e| {
+            RkitError::FileWriteError {
+                path: self.path.clone(),
+                source: e,
+            }
+        })
+    }
+}
+
+fn create_parent(path: &Path) -> RkitResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| RkitError::DirectoryCreationError {
+            path: parent.to_path_buf(),
+            source: e,
+        })?;
+    }
+    Ok(())
+}
+
+/// Records a visit to `repo` in the usage store at `store`. Failures are only
+/// logged, as usage tracking must never break the command being run.
+pub fn record_visit(store: &Path, repo: &Path) {
+    if let Err(e) = Usage::update(store, |usage| usage.record(repo)) {
+        log::warn!("Failed to record visit to {}: {}", repo.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_usage_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        let repo = dir.path().join("repo");
+        let removed = dir.path().join("removed");
+        fs::create_dir_all(&repo).unwrap();
+
+        let mut usage = Usage::load(&path);
+        assert_eq!(usage.last_visited(&repo), None);
+        usage.record(&repo);
+        usage.record(&removed);
+        usage.save().unwrap();
+
+        let mut usage = Usage::load(&path);
+        assert!(usage.last_visited(&repo).unwrap() > 0);
+        assert_eq!(usage.last_visited(&removed), None);
+        usage.record(&repo);
+        assert_eq!(usage.get(&repo).unwrap().rank, 2.0);
+    }
+
+    #[test]
+    fn test_frecency() {
+        let now = now();
+        let visit = |age: u64, rank: f64| RepoUsage {
+            last_visited: now - age,
+            rank,
+        };
+        assert_eq!(visit(60, 1.0).frecency(now), 4.0);
+        assert_eq!(visit(2 * HOUR, 1.0).frecency(now), 2.0);
+        assert_eq!(visit(2 * DAY, 4.0).frecency(now), 2.0);
+        assert_eq!(visit(2 * WEEK, 4.0).frecency(now), 1.0);
+
+        let dir = tempdir().unwrap();
+        let mut usage = Usage::load(&dir.path().join("usage.json"));
+        let daily = dir.path().join("daily");
+        let once = dir.path().join("once");
+        let stale = dir.path().join("stale");
+        usage.repos.insert(daily.clone(), visit(HOUR / 2, 10.0));
+        usage.repos.insert(once.clone(), visit(HOUR / 2, 1.0));
+        usage.repos.insert(stale.clone(), visit(4 * WEEK, 20.0));
+        let ranked: Vec<&Path> = usage.ranked().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(
+            ranked,
+            vec![daily.as_path(), stale.as_path(), once.as_path()]
+        );
+    }
+
+    #[test]
+    fn test_usage_aging() {
+        let dir = tempdir().unwrap();
+        let mut usage = Usage::load(&dir.path().join("usage.json"));
+        let busy = dir.path().join("busy");
+        let rare = dir.path().join("rare");
+        usage.repos.insert(
+            busy.clone(),
+            RepoUsage {
+                last_visited: now(),
+                rank: MAX_TOTAL_RANK,
+            },
+        );
+        usage.record(&rare);
+        assert_eq!(usage.get(&rare), None);
+        assert!(usage.get(&busy).unwrap().rank < MAX_TOTAL_RANK);
+    }
+
+    #[test]
+    fn test_concurrent_visits() {
+        let dir = tempdir().unwrap();
+        let store = dir.path().join("usage.json");
+        let repos: Vec<PathBuf> = (0..8).map(|i| dir.path().join(i.to_string())).collect();
+        for repo in &repos {
+            fs::create_dir_all(repo).unwrap();
+        }
+
+        std::thread::scope(|scope| {
+            for repo in &repos {
+                let store = &store;
+                scope.spawn(move || record_visit(store, repo));
+            }
+        });
+
+        let usage = Usage::load(&store);
+        for repo in &repos {
+            assert_eq!(usage.get(repo).unwrap().rank, 1.0, "{}", repo.display());
+        }
+    }
+
+    #[test]
+    fn test_usage_unreadable_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        fs::write(&path, "not json").unwrap();
+        let usage = Usage::load(&path);
+        assert_eq!(usage.last_visited(dir.path()), None);
+    }
+}
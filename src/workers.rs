@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Runs `f` over every item on at most `threads` worker threads and returns
+/// the results in the same order as the input
+pub fn run_parallel<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_parallel_preserves_order() {
+        let items: Vec<usize> = (0..100).collect();
+        let results = run_parallel(&items, 8, |n| n * 2);
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_parallel_empty() {
+        let items: Vec<usize> = Vec::new();
+        let results = run_parallel(&items, 4, |n| *n);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_run_parallel_zero_threads() {
+        let items = vec![1, 2, 3];
+        let results = run_parallel(&items, 0, |n| n + 1);
+        assert_eq!(results, vec![2, 3, 4]);
+    }
+}
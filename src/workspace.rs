@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CacheError;
+use crate::config::DEFAULT_ROOT;
+use crate::error::{RkitError, RkitResult};
+use crate::scanner::{PerformanceMetrics, RepoScanner, WalkerConfig};
+use crate::CACHE;
+
+/// A named directory holding repositories together with the walker settings
+/// used to scan it
+#[derive(Debug, Clone)]
+pub struct ProjectRoot {
+    pub name: String,
+    pub path: PathBuf,
+    pub walker: WalkerConfig,
+}
+
+impl ProjectRoot {
+    pub fn new(name: &str, path: &Path, walker: WalkerConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            walker,
+        }
+    }
+}
+
+/// Every project root commands operate on, in configured order. The first
+/// root is the default clone target.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    roots: Vec<ProjectRoot>,
+    threads: usize,
+}
+
+impl Workspace {
+    pub fn new(roots: Vec<ProjectRoot>) -> RkitResult<Self> {
+        if roots.is_empty() {
+            return Err(RkitError::ConfigError(
+                "At least one project root is required".to_string(),
+            ));
+        }
+        Ok(Self {
+            roots,
+            threads: WalkerConfig::default().threads,
+        })
+    }
+
+    /// Sets the worker threads used for operations spanning the workspace
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// A workspace with a single root named `default`
+    pub fn single(path: &Path, walker: Option<WalkerConfig>) -> Self {
+        Self {
+            roots: vec![ProjectRoot::new(
+                DEFAULT_ROOT,
+                path,
+                walker.unwrap_or_default(),
+            )],
+            threads: WalkerConfig::default().threads,
+        }
+    }
+
+    pub fn roots(&self) -> &[ProjectRoot] {
+        &self.roots
+    }
+
+    /// The root clones go to when no rule selects another one
+    pub fn default_root(&self) -> &ProjectRoot {
+        &self.roots[0]
+    }
+
+    pub fn root(&self, name: &str) -> RkitResult<&ProjectRoot> {
+        self.roots.iter().find(|r| r.name == name).ok_or_else(|| {
+            log::error!("Unknown root: {}", name);
+            RkitError::ConfigError(format!(
+                "Unknown root '{}', expected one of: {}",
+                name,
+                self.roots
+                    .iter()
+                    .map(|r| r.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+    }
+
+    /// Worker threads used for operations spanning the whole workspace,
+    /// independent of the walker settings of any one root
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The root containing `path`, preferring the innermost one when roots
+    /// are nested
+    pub fn root_of(&self, path: &Path) -> Option<&ProjectRoot> {
+        self.roots
+            .iter()
+            .filter(|r| path.starts_with(&r.path))
+            .max_by_key(|r| r.path.components().count())
+    }
+
+    /// `path` relative to the root containing it
+    pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        self.root_of(path)
+            .and_then(|root| path.strip_prefix(&root.path).ok())
+            .unwrap_or(path)
+    }
+
+    /// `path` as shown to the user: absolute with `full`, otherwise relative
+    /// to its root
+    pub fn display(&self, path: &Path, full: bool) -> String {
+        if full {
+            path.display().to_string()
+        } else {
+            self.relative(path).display().to_string()
+        }
+    }
+
+    /// Resolves a path given on the command line: absolute paths are used
+    /// as is, relative ones are looked up in every root in order
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+        self.roots
+            .iter()
+            .map(|root| root.path.join(path))
+            .find(|candidate| candidate.exists())
+            .unwrap_or_else(|| self.default_root().path.join(path))
+    }
+
+    /// Repositories in every root, served from each root's cached index
+    /// unless `refresh` is set
+    pub fn find_repos(&self, refresh: bool) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
+        self.roots
+            .iter()
+            .flat_map(|root| find_repos(&root.path, Some(root.walker.clone()), refresh))
+            .filter(|repo| seen.insert(repo.clone()))
+            .collect()
+    }
+}
+
+/// Drops expired entries from the cache before it is consulted
+pub(crate) fn refresh_cache() {
+    if let Err(e) = CACHE.validate_and_update() {
+        match e {
+            CacheError::LockError(msg) => log::warn!("Failed to acquire cache lock: {}", msg),
+            CacheError::DirectoryError(e) => log::warn!("Failed to access cache directory: {}", e),
+            CacheError::IoError(e) => log::warn!("Failed to write cache: {}", e),
+            e => log::warn!("Failed to update cache: {}", e),
+        }
+    }
+}
+
+/// Records the repositories found by a scan in the cache
+pub(crate) fn cache_scan(
+    project_root: &Path,
+    scan_key: &str,
+    discovered_repos: &[PathBuf],
+    metrics: &PerformanceMetrics,
+) {
+    // A scan cut short by max_repos is not a complete index of the root, so
+    // only refresh the individual entries in that case
+    let cache_result = if metrics.limit_reached {
+        CACHE.update_and_save_many(discovered_repos)
+    } else {
+        CACHE.update_index(
+            project_root,
+            scan_key,
+            discovered_repos,
+            &metrics.ignore_files,
+        )
+    };
+
+    if let Err(e) = cache_result {
+        match e {
+            CacheError::LockError(msg) => log::warn!("Failed to acquire cache lock: {}", msg),
+            CacheError::DirectoryError(e) => {
+                log::warn!("Failed to access cache directory: {}", e)
+            }
+            CacheError::IoError(e) => log::warn!("Failed to write cache: {}", e),
+            e => log::warn!("Failed to save discovered repositories to cache: {}", e),
+        }
+    }
+}
+
+/// Returns every repository under the project root, from the cached index
+/// when it is fresh and `refresh` is not set, or from a new scan otherwise.
+/// Used by commands that operate on the whole workspace.
+pub fn find_repos(
+    project_root: &Path,
+    config: Option<WalkerConfig>,
+    refresh: bool,
+) -> Vec<PathBuf> {
+    let config = config.unwrap_or_default();
+    let scan_key = config.scan_key();
+
+    refresh_cache();
+
+    if !refresh {
+        if let Some(cached_entries) = CACHE.get_index(project_root, &scan_key) {
+            let limit = config.max_repos.unwrap_or(usize::MAX);
+            return cached_entries
+                .into_iter()
+                .take(limit)
+                .map(|entry| entry.path)
+                .collect();
+        }
+    }
+
+    let result = RepoScanner::new(config).scan(project_root);
+    let repos: Vec<PathBuf> = result.repos.into_iter().map(|repo| repo.path).collect();
+    cache_scan(project_root, &scan_key, &repos, &result.metrics);
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn workspace(paths: &[(&str, PathBuf)]) -> Workspace {
+        Workspace::new(
+            paths
+                .iter()
+                .map(|(name, path)| ProjectRoot::new(name, path, WalkerConfig::default()))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_repos_across_roots() {
+        let dir = tempdir().unwrap();
+        let work = dir.path().join("work");
+        let archive = dir.path().join("archive");
+        let repo1 = work.join("github.com").join("org").join("a");
+        let repo2 = archive.join("github.com").join("org").join("b");
+        fs::create_dir_all(repo1.join(".git")).unwrap();
+        fs::create_dir_all(repo2.join(".git")).unwrap();
+
+        let workspace = workspace(&[("work", work.clone()), ("archive", archive.clone())]);
+        let mut repos = workspace.find_repos(true);
+        repos.sort();
+        assert_eq!(repos, vec![repo2.clone(), repo1.clone()]);
+
+        assert_eq!(workspace.root_of(&repo2).unwrap().name, "archive");
+        assert_eq!(workspace.display(&repo1, false), "github.com/org/a");
+        assert_eq!(workspace.resolve(Path::new("github.com/org/b")), repo2);
+        assert_eq!(workspace.default_root().name, "work");
+        assert!(workspace.root("missing").is_err());
+    }
+
+    #[test]
+    fn test_find_repos_cached() {
+        let dir = tempdir().unwrap();
+        let repo1 = dir.path().join("repo1");
+        let repo2 = dir.path().join("repo2");
+        fs::create_dir_all(repo1.join(".git")).unwrap();
+        fs::create_dir_all(repo2.join(".git")).unwrap();
+
+        let mut repos = find_repos(dir.path(), None, true);
+        repos.sort();
+        assert_eq!(repos, vec![repo1.clone(), repo2.clone()]);
+
+        // Served from the index built by the previous scan
+        let mut repos = find_repos(dir.path(), None, false);
+        repos.sort();
+        assert_eq!(repos, vec![repo1, repo2]);
+    }
+
+    #[test]
+    fn test_nested_roots() {
+        let dir = tempdir().unwrap();
+        let outer = dir.path().join("projects");
+        let inner = outer.join("archive");
+        let workspace = workspace(&[("main", outer), ("archive", inner.clone())]);
+        let repo = inner.join("org").join("repo");
+        assert_eq!(workspace.root_of(&repo).unwrap().name, "archive");
+        assert_eq!(workspace.relative(&repo), Path::new("org/repo"));
+    }
+
+    #[test]
+    fn test_threads_are_workspace_wide() {
+        let walker = WalkerConfig {
+            threads: 3,
+            ..Default::default()
+        };
+        let roots = vec![ProjectRoot::new("work", Path::new("/work"), walker)];
+        let workspace = Workspace::new(roots).unwrap();
+        assert_eq!(workspace.threads(), WalkerConfig::default().threads);
+        assert_eq!(workspace.with_threads(5).threads(), 5);
+    }
+}